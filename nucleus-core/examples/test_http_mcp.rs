@@ -6,7 +6,7 @@
 //! 3. List available tools
 //! 4. Make a sample request (get Bitcoin price)
 
-use nucleus_core::mcp::transport::http::HttpTransport;
+use nucleus_core::mcp::McpClient;
 use serde_json::json;
 
 #[tokio::main]
@@ -21,8 +21,8 @@ async fn main() -> anyhow::Result<()> {
     let server_url = "https://mcp.api.coingecko.com/mcp";
     println!("📍 Connecting to: {}\n", server_url);
 
-    // Create HTTP transport
-    let mut transport = HttpTransport::new(server_url);
+    // Create MCP client over HTTP transport
+    let client = McpClient::new_http(server_url);
 
     // Step 1: Initialize MCP connection
     // MCP protocol requires an initialize handshake
@@ -39,7 +39,7 @@ async fn main() -> anyhow::Result<()> {
         }
     });
 
-    match transport.request("initialize", Some(init_params)).await {
+    match client.request("initialize", Some(init_params)).await {
         Ok(response) => {
             println!("✅ Initialize successful!");
             println!("Response: {}\n", serde_json::to_string_pretty(&response)?);
@@ -55,7 +55,7 @@ async fn main() -> anyhow::Result<()> {
 
     // Step 2: Send initialized notification
     println!("📡 Step 2: Sending initialized notification...");
-    match transport.notify("notifications/initialized", None).await {
+    match client.notify("notifications/initialized", None).await {
         Ok(_) => println!("✅ Initialized notification sent\n"),
         Err(e) => {
             println!("⚠️  Warning: Failed to send initialized notification: {}\n", e);
@@ -64,7 +64,7 @@ async fn main() -> anyhow::Result<()> {
 
     // Step 3: List available tools
     println!("📡 Step 3: Listing available tools...");
-    match transport.request("tools/list", None).await {
+    match client.request("tools/list", None).await {
         Ok(response) => {
             println!("✅ Tools listed successfully!");
             
@@ -106,7 +106,7 @@ async fn main() -> anyhow::Result<()> {
         }
     });
 
-    match transport.request("tools/call", Some(tool_params)).await {
+    match client.request("tools/call", Some(tool_params)).await {
         Ok(response) => {
             println!("✅ Tool call successful!");
             
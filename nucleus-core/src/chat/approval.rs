@@ -0,0 +1,188 @@
+//! Human-in-the-loop approval of tool calls.
+//!
+//! Before a tool runs, the [`ApprovalPolicy`] in `agent.approval` decides
+//! whether it is allowed, refused, or needs approval. Calls that need approval
+//! are passed to an [`ApprovalHandler`] (typically a prompt in the UI), which
+//! can approve them, deny them with a reason, or change their arguments.
+//! Denials are reported to the model as the tool's result.
+
+use crate::config::{ApprovalPolicy, ApprovalRule};
+use crate::provider::ToolCall;
+use async_trait::async_trait;
+use nucleus_plugin::{Permission, PluginRegistry};
+use serde_json::Value;
+use std::future::Future;
+
+/// A tool call waiting for approval.
+#[derive(Debug, Clone)]
+pub struct ApprovalRequest {
+    pub tool_name: String,
+    pub arguments: Value,
+    /// Permissions the tool requires
+    pub permission: Permission,
+}
+
+/// The answer to an [`ApprovalRequest`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApprovalDecision {
+    /// Run the call as requested
+    Approve,
+    /// Don't run the call; the reason is passed on to the model
+    Deny { reason: String },
+    /// Run the call with different arguments
+    Edit { arguments: Value },
+}
+
+/// Decides on tool calls that the policy marks as `ask`.
+///
+/// Implemented for async closures taking an [`ApprovalRequest`], so a
+/// callback can be passed directly:
+///
+/// ```no_run
+/// # use nucleus_core::chat::{ApprovalDecision, ApprovalRequest};
+/// # use nucleus_core::config::ApprovalPolicy;
+/// # use nucleus_core::{ChatManager, Config};
+/// # use nucleus_plugin::{PluginRegistry, Permission};
+/// # use std::sync::Arc;
+/// # async fn example() -> anyhow::Result<()> {
+/// let manager = ChatManager::builder(Config::load_or_default(), PluginRegistry::new(Permission::ALL))
+///     .with_approval_policy(ApprovalPolicy::confirm_changes())
+///     .with_approval_handler(Arc::new(|request: ApprovalRequest| async move {
+///         println!("Allow {} with {}? Denying for now.", request.tool_name, request.arguments);
+///         ApprovalDecision::Deny { reason: "The user declined".to_string() }
+///     }))
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[async_trait]
+pub trait ApprovalHandler: Send + Sync {
+    /// Decide whether the tool call may run
+    async fn approve(&self, request: ApprovalRequest) -> ApprovalDecision;
+}
+
+#[async_trait]
+impl<F, Fut> ApprovalHandler for F
+where
+    F: Fn(ApprovalRequest) -> Fut + Send + Sync,
+    Fut: Future<Output = ApprovalDecision> + Send,
+{
+    async fn approve(&self, request: ApprovalRequest) -> ApprovalDecision {
+        self(request).await
+    }
+}
+
+/// Applies the policy to a tool call, asking `handler` when required.
+///
+/// Unknown tools are approved here; running them reports the error.
+pub(crate) async fn review(
+    policy: &ApprovalPolicy,
+    handler: Option<&dyn ApprovalHandler>,
+    registry: &PluginRegistry,
+    tool_call: &ToolCall,
+) -> ApprovalDecision {
+    let name = &tool_call.function.name;
+    let Some(plugin) = registry.get(name) else {
+        return ApprovalDecision::Approve;
+    };
+
+    let permission = plugin.required_permission();
+    match policy.rule_for(name, &permission) {
+        ApprovalRule::Allow => ApprovalDecision::Approve,
+        ApprovalRule::Deny => ApprovalDecision::Deny {
+            reason: format!("The tool '{}' is not allowed", name),
+        },
+        ApprovalRule::Ask => match handler {
+            Some(handler) => {
+                handler
+                    .approve(ApprovalRequest {
+                        tool_name: name.clone(),
+                        arguments: tool_call.function.arguments.clone(),
+                        permission,
+                    })
+                    .await
+            }
+            None => ApprovalDecision::Deny {
+                reason: format!("The tool '{}' requires approval, but no one is available to approve it", name),
+            },
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::ToolCallFunction;
+    use nucleus_plugin::{Plugin, PluginOutput};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    struct WritePlugin;
+
+    #[async_trait]
+    impl Plugin for WritePlugin {
+        fn name(&self) -> &str {
+            "write_file"
+        }
+
+        fn description(&self) -> &str {
+            "Writes a file"
+        }
+
+        fn parameter_schema(&self) -> Value {
+            json!({"type": "object"})
+        }
+
+        fn required_permission(&self) -> Permission {
+            Permission::READ_WRITE
+        }
+
+        async fn execute(&self, _input: Value) -> nucleus_plugin::Result<PluginOutput> {
+            Ok(PluginOutput::new("written"))
+        }
+    }
+
+    fn write_call() -> ToolCall {
+        ToolCall {
+            id: None,
+            function: ToolCallFunction {
+                name: "write_file".to_string(),
+                arguments: json!({"path": "/etc/passwd"}),
+            },
+        }
+    }
+
+    fn registry() -> PluginRegistry {
+        let mut registry = PluginRegistry::new(Permission::ALL);
+        registry.register(Arc::new(WritePlugin));
+        registry
+    }
+
+    #[tokio::test]
+    async fn test_handler_can_edit_arguments() {
+        let handler = |request: ApprovalRequest| async move {
+            assert_eq!(request.permission, Permission::READ_WRITE);
+            ApprovalDecision::Edit {
+                arguments: json!({"path": "notes.txt"}),
+            }
+        };
+
+        let decision = review(&ApprovalPolicy::confirm_changes(), Some(&handler), &registry(), &write_call()).await;
+        assert_eq!(
+            decision,
+            ApprovalDecision::Edit {
+                arguments: json!({"path": "notes.txt"})
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ask_without_handler_denies() {
+        let decision = review(&ApprovalPolicy::confirm_changes(), None, &registry(), &write_call()).await;
+        assert!(matches!(decision, ApprovalDecision::Deny { .. }));
+
+        let decision = review(&ApprovalPolicy::default(), None, &registry(), &write_call()).await;
+        assert_eq!(decision, ApprovalDecision::Approve);
+    }
+}
@@ -0,0 +1,423 @@
+//! Keeping conversations within the model's context window.
+//!
+//! Before each request the conversation is measured with
+//! [`Provider::count_tokens`] against a budget of `llm.context_length`, minus
+//! `llm.context.response_reserve` and the space taken by the system prompt and
+//! tool definitions. When it doesn't fit, the configured [`ContextStrategy`]s
+//! are applied in order until it does:
+//!
+//! - `truncate_tool_results`: shortens tool results longer than
+//!   `max_tool_result_tokens`, oldest first
+//! - `summarize`: asks the model to summarize earlier turns and replaces them
+//!   with the summary
+//! - `drop_oldest`: removes the oldest turns
+//!
+//! Earlier turns are only ever summarized or dropped as a whole, and the
+//! current turn (the question being answered and the tool calls made for it)
+//! is kept.
+
+use crate::config::{ContextStrategy, LlmConfig};
+use crate::provider::{ChatRequest, Message, Provider, Tool};
+use anyhow::{Context, Result};
+use std::fmt::Write;
+use tracing::{debug, warn};
+
+/// Tokens charged per message for role markers and separators
+const MESSAGE_OVERHEAD: usize = 4;
+
+/// Instructions for the model when summarizing earlier turns
+const SUMMARY_PROMPT: &str = "Summarize the following conversation between a user and an \
+assistant. Keep the user's goals, decisions that were made, file names, facts learned from \
+tool results and open questions. Reply with the summary only.";
+
+/// Prefix of the system message that replaces summarized turns
+const SUMMARY_PREFIX: &str = "Summary of the earlier conversation:\n";
+
+/// Token budget and shrinking strategies for one model.
+#[derive(Debug, Clone)]
+pub(crate) struct ContextWindow {
+    context_length: usize,
+    response_reserve: usize,
+    max_tool_result_tokens: usize,
+    strategies: Vec<ContextStrategy>,
+}
+
+impl ContextWindow {
+    pub(crate) fn new(config: &LlmConfig) -> Self {
+        Self {
+            context_length: config.context_length,
+            response_reserve: config.context.response_reserve,
+            max_tool_result_tokens: config.context.max_tool_result_tokens,
+            strategies: config.context.strategies.clone(),
+        }
+    }
+
+    /// Shrinks `past` and `turn` until they fit next to `system` and `tools`.
+    ///
+    /// `past` holds the earlier turns of the conversation and `turn` the
+    /// messages of the turn in progress. If nothing more can be removed and
+    /// the conversation still doesn't fit, a warning is logged and the
+    /// request is sent anyway.
+    pub(crate) async fn fit(
+        &self,
+        provider: &dyn Provider,
+        model: &str,
+        system: &Message,
+        tools: &[Tool],
+        past: &mut Vec<Message>,
+        turn: &mut [Message],
+    ) {
+        let tools_json = serde_json::to_string(tools).unwrap_or_default();
+        let budget = self
+            .context_length
+            .saturating_sub(self.response_reserve)
+            .saturating_sub(message_tokens(provider, system))
+            .saturating_sub(provider.count_tokens(&tools_json));
+
+        let used = |past: &[Message], turn: &[Message]| -> usize {
+            past.iter()
+                .chain(turn.iter())
+                .map(|message| message_tokens(provider, message))
+                .sum()
+        };
+
+        for strategy in &self.strategies {
+            let tokens = used(past, turn);
+            if tokens <= budget {
+                return;
+            }
+            debug!(tokens, budget, ?strategy, "Conversation exceeds the context window");
+
+            match strategy {
+                ContextStrategy::TruncateToolResults => {
+                    self.truncate_tool_results(provider, past, turn, tokens - budget);
+                }
+                ContextStrategy::Summarize => {
+                    if let Err(e) = self.summarize(provider, model, past).await {
+                        warn!(error = %format!("{:#}", e), "Failed to summarize conversation history");
+                    }
+                }
+                ContextStrategy::DropOldest => {
+                    while used(past, turn) > budget && !past.is_empty() {
+                        let end = next_turn_start(past, 0);
+                        debug!(messages = end, "Dropping oldest turn");
+                        past.drain(..end);
+                    }
+                }
+            }
+        }
+
+        let tokens = used(past, turn);
+        if tokens > budget {
+            warn!(tokens, budget, "Conversation still exceeds the context window");
+        }
+    }
+
+    /// Truncates oversized tool results, oldest first, until `excess` tokens are freed.
+    fn truncate_tool_results(
+        &self,
+        provider: &dyn Provider,
+        past: &mut [Message],
+        turn: &mut [Message],
+        excess: usize,
+    ) {
+        let mut freed = 0;
+        for message in past.iter_mut().chain(turn.iter_mut()) {
+            if freed >= excess {
+                break;
+            }
+            if message.role != "tool" {
+                continue;
+            }
+
+            let tokens = provider.count_tokens(&message.content);
+            if tokens <= self.max_tool_result_tokens {
+                continue;
+            }
+
+            // Token counts aren't proportional to characters, but close enough
+            let chars = message.content.chars().count();
+            let keep = chars * self.max_tool_result_tokens / tokens;
+            let mut content: String = message.content.chars().take(keep).collect();
+            let _ = write!(content, "\n[... truncated {} characters]", chars - keep);
+
+            freed += tokens.saturating_sub(provider.count_tokens(&content));
+            message.content = content;
+        }
+    }
+
+    /// Replaces the earlier turns in `past` with a summary written by the model.
+    ///
+    /// The most recent turn is kept verbatim unless it is the only one.
+    async fn summarize(&self, provider: &dyn Provider, model: &str, past: &mut Vec<Message>) -> Result<()> {
+        let last_turn = past
+            .iter()
+            .rposition(|message| message.role == "user")
+            .unwrap_or_default();
+        let end = if past[..last_turn].iter().any(|message| message.role == "user") {
+            last_turn
+        } else {
+            past.len()
+        };
+        if past[..end].iter().all(|message| message.role == "system") {
+            // Nothing new since the last summary
+            return Ok(());
+        }
+
+        let mut transcript = String::new();
+        for message in &past[..end] {
+            let _ = writeln!(transcript, "{}: {}\n", message.role, message.content);
+            for tool_call in message.tool_calls.iter().flatten() {
+                let _ = writeln!(
+                    transcript,
+                    "{} called {} with {}\n",
+                    message.role, tool_call.function.name, tool_call.function.arguments
+                );
+            }
+        }
+
+        // The transcript itself has to fit; keep its most recent part
+        let limit = self
+            .context_length
+            .saturating_sub(self.response_reserve)
+            .saturating_sub(provider.count_tokens(SUMMARY_PROMPT));
+        let tokens = provider.count_tokens(&transcript);
+        if tokens > limit {
+            let chars = transcript.chars().count();
+            let skip = chars - chars * limit / tokens;
+            transcript = transcript.chars().skip(skip).collect();
+        }
+
+        let request = ChatRequest::new(
+            model,
+            vec![Message::system(None, SUMMARY_PROMPT), Message::user(None, transcript)],
+        )
+        .with_temperature(0.2);
+
+        let mut summary = String::new();
+        provider
+            .chat(request, Box::new(|response| summary.push_str(&response.content)))
+            .await
+            .context("Summary request failed")?;
+
+        let summary = summary.trim();
+        if summary.is_empty() {
+            anyhow::bail!("Model returned an empty summary");
+        }
+
+        debug!(messages = end, "Summarized earlier turns");
+        past.splice(..end, [Message::system(None, format!("{}{}", SUMMARY_PREFIX, summary))]);
+        Ok(())
+    }
+}
+
+/// Builds the messages of a request from the system prompt and the conversation.
+///
+/// A summary left at the start of `past` is merged into the system prompt,
+/// since many chat templates only accept a single leading system message.
+pub(crate) fn request_messages(system: &Message, past: &[Message], turn: &[Message]) -> Vec<Message> {
+    let summaries = past
+        .iter()
+        .take_while(|message| message.role == "system")
+        .count();
+
+    let mut system = system.clone();
+    for summary in &past[..summaries] {
+        system.content.push_str("\n\n");
+        system.content.push_str(&summary.content);
+    }
+
+    let mut messages = Vec::with_capacity(1 + past.len() - summaries + turn.len());
+    messages.push(system);
+    messages.extend(past[summaries..].iter().cloned());
+    messages.extend(turn.iter().cloned());
+    messages
+}
+
+/// Tokens a message occupies, including its tool calls and, if the provider
+/// sends it, its reasoning.
+fn message_tokens(provider: &dyn Provider, message: &Message) -> usize {
+    let tool_calls = message
+        .tool_calls
+        .iter()
+        .flatten()
+        .map(|tool_call| {
+            provider.count_tokens(&tool_call.function.name)
+                + provider.count_tokens(&tool_call.function.arguments.to_string())
+        })
+        .sum::<usize>();
+
+    let reasoning = match &message.reasoning {
+        Some(reasoning) if provider.sends_reasoning() => provider.count_tokens(reasoning),
+        _ => 0,
+    };
+
+    MESSAGE_OVERHEAD + provider.count_tokens(&message.content) + reasoning + tool_calls
+}
+
+/// Index of the first message after the turn starting at `start`.
+///
+/// Messages before the first user message (such as a summary) count as a turn of their own.
+fn next_turn_start(messages: &[Message], start: usize) -> usize {
+    messages[start + 1..]
+        .iter()
+        .position(|message| message.role == "user")
+        .map_or(messages.len(), |offset| start + 1 + offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ContextConfig;
+    use crate::models::EmbeddingModel;
+    use crate::provider::{ChatResponse, Result as ProviderResult};
+    use async_trait::async_trait;
+
+    /// Answers every request with a fixed summary
+    struct SummaryProvider;
+
+    #[async_trait]
+    impl Provider for SummaryProvider {
+        async fn chat<'a>(
+            &'a self,
+            request: ChatRequest,
+            mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+        ) -> ProviderResult<()> {
+            assert!(request.messages[1].content.contains("user: first question"));
+            callback(ChatResponse {
+                model: request.model,
+                content: "The user asked two questions.".to_string(),
+                reasoning: String::new(),
+                done: true,
+                message: Message::assistant(None, "The user asked two questions."),
+                usage: None,
+            });
+            Ok(())
+        }
+
+        async fn embed(&self, _text: &str, _model: &EmbeddingModel) -> ProviderResult<Vec<f32>> {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Sends reasoning back to the model, like Ollama
+    struct ReasoningProvider;
+
+    #[async_trait]
+    impl Provider for ReasoningProvider {
+        async fn chat<'a>(
+            &'a self,
+            _request: ChatRequest,
+            _callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+        ) -> ProviderResult<()> {
+            unreachable!("only used for counting tokens")
+        }
+
+        async fn embed(&self, _text: &str, _model: &EmbeddingModel) -> ProviderResult<Vec<f32>> {
+            Ok(Vec::new())
+        }
+
+        fn sends_reasoning(&self) -> bool {
+            true
+        }
+    }
+
+    fn window(context_length: usize, strategies: Vec<ContextStrategy>) -> ContextWindow {
+        ContextWindow::new(&LlmConfig {
+            context_length,
+            context: ContextConfig {
+                response_reserve: 0,
+                max_tool_result_tokens: 10,
+                strategies,
+            },
+            ..LlmConfig::default()
+        })
+    }
+
+    fn history() -> Vec<Message> {
+        vec![
+            Message::user(None, "first question"),
+            Message::assistant(None, "first answer"),
+            Message::user(None, "second question"),
+            Message::tool(None, "x".repeat(400)),
+            Message::assistant(None, "second answer"),
+        ]
+    }
+
+    #[test]
+    fn test_reasoning_counts_only_when_sent() {
+        let answer = Message::assistant(None, "answer");
+        let mut with_reasoning = answer.clone();
+        with_reasoning.reasoning = Some("x".repeat(400));
+
+        assert_eq!(message_tokens(&SummaryProvider, &with_reasoning), message_tokens(&SummaryProvider, &answer));
+        assert_eq!(
+            message_tokens(&ReasoningProvider, &with_reasoning),
+            message_tokens(&ReasoningProvider, &answer) + 100
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fitting_conversation_is_unchanged() {
+        let system = Message::system(None, "system");
+        let mut past = history();
+        let mut turn = vec![Message::user(None, "third question")];
+
+        window(10_000, ContextConfig::default().strategies)
+            .fit(&SummaryProvider, "model", &system, &[], &mut past, &mut turn)
+            .await;
+
+        assert_eq!(past.len(), 5);
+        assert_eq!(past[3].content.len(), 400);
+    }
+
+    #[tokio::test]
+    async fn test_truncate_tool_results() {
+        let system = Message::system(None, "system");
+        let mut past = history();
+        let mut turn = vec![Message::user(None, "third question")];
+
+        window(100, vec![ContextStrategy::TruncateToolResults])
+            .fit(&SummaryProvider, "model", &system, &[], &mut past, &mut turn)
+            .await;
+
+        assert_eq!(past.len(), 5);
+        assert!(past[3].content.starts_with(&"x".repeat(40)));
+        assert!(past[3].content.ends_with("[... truncated 360 characters]"));
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_removes_whole_turns() {
+        let system = Message::system(None, "system");
+        let mut past = history();
+        let mut turn = vec![Message::user(None, "third question")];
+
+        window(140, vec![ContextStrategy::DropOldest])
+            .fit(&SummaryProvider, "model", &system, &[], &mut past, &mut turn)
+            .await;
+
+        assert_eq!(past.len(), 3);
+        assert_eq!(past[0].content, "second question");
+        assert_eq!(turn.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_summarize_keeps_last_turn() {
+        let system = Message::system(None, "system");
+        let mut past = history();
+        let mut turn = vec![Message::user(None, "third question")];
+
+        window(100, vec![ContextStrategy::Summarize])
+            .fit(&SummaryProvider, "model", &system, &[], &mut past, &mut turn)
+            .await;
+
+        assert_eq!(past.len(), 4);
+        assert_eq!(past[0].role, "system");
+        assert_eq!(past[1].content, "second question");
+
+        let messages = request_messages(&system, &past, &turn);
+        assert_eq!(messages.len(), 5);
+        assert!(messages[0].content.ends_with("The user asked two questions."));
+        assert_eq!(messages[1].content, "second question");
+    }
+}
@@ -0,0 +1,196 @@
+//! Progress events emitted while a query runs.
+//!
+//! [`ChatEvent`]s describe everything that happens during a query: text and
+//! reasoning as it streams, the knowledge base context that was retrieved,
+//! each tool call from request to result, token usage and the final answer.
+//! They are available as a callback through
+//! [`ChatManager::query_with_events`](super::ChatManager::query_with_events)
+//! or as a stream through [`ChatManager::query_events`](super::ChatManager::query_events).
+
+use super::limits::QueryResult;
+use crate::provider::Usage;
+use futures::channel::mpsc;
+use futures::future::Future;
+use futures::stream::{self, BoxStream, StreamExt};
+use serde_json::Value;
+use std::time::Duration;
+
+const THINK_START: &str = "<think>";
+const THINK_END: &str = "</think>";
+
+/// Something that happened while answering a query.
+#[derive(Debug, Clone)]
+pub enum ChatEvent {
+    /// Knowledge base chunks were added to the query as context
+    ContextRetrieved { sources: Vec<ContextSource> },
+    /// A piece of the answer
+    TextDelta { text: String },
+    /// A piece of the model's reasoning; only emitted if `llm.reasoning.stream` is set
+    ReasoningDelta { text: String },
+    /// The model asked for a tool call
+    ToolCallRequested {
+        /// Position of the call among all tool calls of the query
+        index: usize,
+        name: String,
+        arguments: Value,
+    },
+    /// A requested tool was refused by the approval policy or handler; the
+    /// reason was sent to the model
+    ToolCallDenied {
+        index: usize,
+        name: String,
+        reason: String,
+    },
+    /// A requested tool started running
+    ToolCallStarted {
+        index: usize,
+        name: String,
+        /// The arguments used, which differ from the requested ones if they
+        /// were edited during approval
+        arguments: Value,
+    },
+    /// A tool call completed; its output was sent to the model
+    ToolCallFinished {
+        index: usize,
+        name: String,
+        output: String,
+        duration: Duration,
+    },
+    /// A tool call failed; the error was sent to the model
+    ToolCallFailed {
+        index: usize,
+        name: String,
+        error: String,
+        duration: Duration,
+    },
+    /// Tokens used by one model response
+    Usage(Usage),
+    /// The query completed
+    Final(QueryResult),
+    /// The query failed; only emitted by the stream API, where there is no
+    /// `Result` to return the error in
+    Error { message: String },
+}
+
+/// A knowledge base chunk used as context.
+#[derive(Debug, Clone)]
+pub struct ContextSource {
+    /// The `source` metadata of the chunk, usually a file path
+    pub source: Option<String>,
+    /// Similarity to the query
+    pub score: f32,
+    /// The chunk's text
+    pub content: String,
+}
+
+/// Adapts a text callback to events, passing on text and reasoning deltas.
+///
+/// Reasoning is wrapped in `<think>` tags again, so the callback sees the
+/// text as the model produced it. The block is closed by the next event that
+/// isn't reasoning, so a response that ends in reasoning, e.g. before tool
+/// calls or a cancellation, is still closed by [`ChatEvent::Final`].
+pub(crate) fn text_chunks<F>(mut on_chunk: F) -> impl FnMut(ChatEvent) + Send
+where
+    F: FnMut(&str) + Send,
+{
+    let mut in_think = false;
+    move |event| {
+        if in_think && !matches!(event, ChatEvent::ReasoningDelta { .. }) {
+            on_chunk(THINK_END);
+            in_think = false;
+        }
+
+        match event {
+            ChatEvent::ReasoningDelta { text } => {
+                if !in_think {
+                    on_chunk(THINK_START);
+                    in_think = true;
+                }
+                on_chunk(&text);
+            }
+            ChatEvent::TextDelta { text } => on_chunk(&text),
+            _ => {}
+        }
+    }
+}
+
+/// Runs a query that reports events through a callback as a stream of events.
+///
+/// The query runs as the stream is polled. If it fails, the stream ends with
+/// [`ChatEvent::Error`].
+pub(crate) fn event_stream<'a, R, Fut>(run: R) -> BoxStream<'a, ChatEvent>
+where
+    R: FnOnce(Box<dyn FnMut(ChatEvent) + Send + 'a>) -> Fut,
+    Fut: Future<Output = anyhow::Result<QueryResult>> + Send + 'a,
+{
+    let (sender, receiver) = mpsc::unbounded();
+    let errors = sender.clone();
+    let query = run(Box::new(move |event| {
+        let _ = sender.unbounded_send(event);
+    }));
+
+    let driver = async move {
+        if let Err(e) = query.await {
+            let _ = errors.unbounded_send(ChatEvent::Error {
+                message: format!("{:#}", e),
+            });
+        }
+    };
+
+    // The driver yields no items; it only feeds the channel. Both senders are
+    // dropped when it completes, which ends the receiver.
+    stream::select(stream::once(driver).filter_map(|()| async { None }), receiver).boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_chunks_restores_tags() {
+        let mut text = String::new();
+        let mut on_event = text_chunks(|chunk| text.push_str(chunk));
+        on_event(ChatEvent::ReasoningDelta { text: "hm".to_string() });
+        on_event(ChatEvent::ReasoningDelta { text: "m".to_string() });
+        on_event(ChatEvent::TextDelta { text: "Hi".to_string() });
+        drop(on_event);
+
+        assert_eq!(text, "<think>hmm</think>Hi");
+    }
+
+    #[test]
+    fn test_text_chunks_closes_trailing_reasoning() {
+        let mut text = String::new();
+        let mut on_event = text_chunks(|chunk| text.push_str(chunk));
+        on_event(ChatEvent::ReasoningDelta { text: "hmm".to_string() });
+        on_event(ChatEvent::ToolCallRequested {
+            index: 0,
+            name: "read_file".to_string(),
+            arguments: serde_json::json!({}),
+        });
+        on_event(ChatEvent::ReasoningDelta { text: "ok".to_string() });
+        on_event(ChatEvent::Final(QueryResult {
+            content: String::new(),
+            reasoning: None,
+            limit_reached: None,
+            usage: Usage::default(),
+            cancelled: true,
+        }));
+        drop(on_event);
+
+        assert_eq!(text, "<think>hmm</think><think>ok</think>");
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_reports_error_last() {
+        let events: Vec<ChatEvent> = event_stream(|mut on_event| async move {
+            on_event(ChatEvent::TextDelta { text: "partial".to_string() });
+            anyhow::bail!("provider went away")
+        })
+        .collect()
+        .await;
+
+        assert!(matches!(&events[0], ChatEvent::TextDelta { text } if text == "partial"));
+        assert!(matches!(&events[1], ChatEvent::Error { message } if message == "provider went away"));
+    }
+}
@@ -0,0 +1,156 @@
+//! Limits on the tool-calling loop of a query.
+//!
+//! Without limits, a model that keeps calling tools would never finish. The
+//! loop is bounded by the number of tool rounds (model responses that call
+//! tools), the total number of tool calls, the number of failed rounds in a
+//! row and a wall-clock deadline, all set in [`AgentConfig`]. When a limit is reached, the model is asked to answer
+//! with what it has gathered so far, and the [`QueryResult`] records which
+//! limit ended the loop.
+
+use crate::config::AgentConfig;
+use crate::provider::Usage;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Outcome of a query.
+#[derive(Debug, Clone)]
+pub struct QueryResult {
+    /// The final answer
+    pub content: String,
+    /// The model's reasoning behind the final answer, if `llm.reasoning.keep` is set
+    pub reasoning: Option<String>,
+    /// Limit that ended the tool loop early, or `None` if the model finished on its own
+    pub limit_reached: Option<AgentLimit>,
+    /// Tokens used by all model responses of the query, as far as the provider reports them
+    pub usage: Usage,
+    /// Whether the query was cancelled; `content` then holds what was generated until then
+    pub cancelled: bool,
+}
+
+/// A limit of the tool-calling loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AgentLimit {
+    /// `agent.max_tool_rounds` model responses called tools
+    ToolRounds(usize),
+    /// `agent.max_tool_calls` tools were called in total
+    ToolCalls(usize),
+    /// `agent.max_consecutive_tool_failures` tool rounds failed in a row
+    ToolFailures(usize),
+    /// The query ran for `agent.timeout_secs`
+    Deadline(Duration),
+}
+
+impl fmt::Display for AgentLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ToolRounds(max) => write!(f, "maximum of {} tool rounds", max),
+            Self::ToolCalls(max) => write!(f, "maximum of {} tool calls", max),
+            Self::ToolFailures(max) => write!(f, "maximum of {} failed tool rounds in a row", max),
+            Self::Deadline(timeout) => write!(f, "time limit of {} seconds", timeout.as_secs()),
+        }
+    }
+}
+
+/// Tracks how much of its limits a query has used.
+#[derive(Debug)]
+pub(crate) struct AgentBudget {
+    max_tool_rounds: usize,
+    max_tool_calls: usize,
+    timeout: Option<Duration>,
+    started: Instant,
+    tool_rounds: usize,
+    tool_calls: usize,
+}
+
+impl AgentBudget {
+    /// Starts the clock for a new query.
+    pub(crate) fn new(config: &AgentConfig) -> Self {
+        Self {
+            max_tool_rounds: config.max_tool_rounds,
+            max_tool_calls: config.max_tool_calls,
+            timeout: config.timeout_secs.map(Duration::from_secs),
+            started: Instant::now(),
+            tool_rounds: 0,
+            tool_calls: 0,
+        }
+    }
+
+    /// Returns the deadline limit if the query has run out of time.
+    pub(crate) fn check_deadline(&self) -> Option<AgentLimit> {
+        self.timeout
+            .filter(|timeout| self.started.elapsed() >= *timeout)
+            .map(AgentLimit::Deadline)
+    }
+
+    /// Time left until the deadline, or `None` if there is no deadline.
+    pub(crate) fn remaining(&self) -> Option<Duration> {
+        self.timeout
+            .map(|timeout| timeout.saturating_sub(self.started.elapsed()))
+    }
+
+    /// Records a round of `calls` tool calls, unless it would exceed a limit.
+    ///
+    /// A round that doesn't fit is not recorded and none of its calls
+    /// should be executed.
+    pub(crate) fn start_round(&mut self, calls: usize) -> Result<(), AgentLimit> {
+        if let Some(limit) = self.check_deadline() {
+            return Err(limit);
+        }
+        if self.tool_rounds >= self.max_tool_rounds {
+            return Err(AgentLimit::ToolRounds(self.max_tool_rounds));
+        }
+        if self.tool_calls + calls > self.max_tool_calls {
+            return Err(AgentLimit::ToolCalls(self.max_tool_calls));
+        }
+
+        self.tool_rounds += 1;
+        self.tool_calls += calls;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(max_tool_rounds: usize, max_tool_calls: usize, timeout_secs: Option<u64>) -> AgentConfig {
+        AgentConfig {
+            max_tool_rounds,
+            max_tool_calls,
+            timeout_secs,
+            ..AgentConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_tool_round_limit() {
+        let mut budget = AgentBudget::new(&config(2, 100, None));
+        assert!(budget.start_round(1).is_ok());
+        assert!(budget.start_round(1).is_ok());
+        assert_eq!(budget.start_round(1), Err(AgentLimit::ToolRounds(2)));
+    }
+
+    #[test]
+    fn test_tool_call_limit_counts_whole_rounds() {
+        let mut budget = AgentBudget::new(&config(10, 5, None));
+        assert!(budget.start_round(3).is_ok());
+        assert_eq!(budget.start_round(3), Err(AgentLimit::ToolCalls(5)));
+        assert!(budget.start_round(2).is_ok());
+    }
+
+    #[test]
+    fn test_deadline() {
+        let mut budget = AgentBudget::new(&config(10, 10, Some(0)));
+        assert_eq!(budget.check_deadline(), Some(AgentLimit::Deadline(Duration::ZERO)));
+        assert!(budget.start_round(1).is_err());
+
+        assert_eq!(budget.remaining(), Some(Duration::ZERO));
+
+        let budget = AgentBudget::new(&config(10, 10, None));
+        assert_eq!(budget.check_deadline(), None);
+        assert_eq!(budget.remaining(), None);
+
+        let budget = AgentBudget::new(&config(10, 10, Some(60)));
+        assert!(budget.remaining().is_some_and(|remaining| remaining > Duration::from_secs(50)));
+    }
+}
@@ -27,15 +27,32 @@
 //! while the final `done=true` chunk contains no tool calls. The manager
 //! preserves tool calls from any chunk to ensure they're not lost.
 
-use crate::config::Config;
+use super::approval::{review, ApprovalDecision, ApprovalHandler};
+use super::context::{request_messages, ContextWindow};
+use super::events::{event_stream, text_chunks, ChatEvent, ContextSource};
+use super::limits::{AgentBudget, AgentLimit, QueryResult};
+use super::prompt::build_system_prompt;
+use super::session::{ChatSession, SessionStore, SessionSummary};
+use super::tools::{execute_tool, is_read_only, parallel_batches, ToolError};
+use crate::config::{ApprovalPolicy, Config, ReasoningConfig};
+use crate::mcp::{register_configured_servers, ProviderSamplingHandler};
 use crate::models::EmbeddingModel;
-use crate::provider::{ChatRequest, ChatResponse, Message, MistralRsProvider, Provider, Tool, ToolCall, ToolFunction};
+use crate::provider::{
+    ChatRequest, ChatResponse, Message, Provider, ProviderError, ProviderFactory, Tool, ToolCall, ToolFunction, Usage,
+};
 use crate::rag::RagEngine;
-use nucleus_plugin::PluginRegistry;
+use nucleus_plugin::{PluginOutput, PluginRegistry};
 use anyhow::{Context, Result};
+use futures::stream::{self, BoxStream, StreamExt};
 use std::path::Path;
-use std::sync::Arc;
-use tracing::{debug, info};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// How long the final answer may take once the query's deadline has passed
+const FINAL_ANSWER_GRACE_PERIOD: Duration = Duration::from_secs(60);
 
 /// Manages multi-turn conversations with tool-augmented LLM capabilities.
 ///
@@ -63,15 +80,20 @@ use tracing::{debug, info};
 /// # Tool Execution
 ///
 /// When the LLM requests a tool, the manager:
-/// 1. Adds the assistant message with tool calls to conversation history
-/// 2. Executes each tool via the plugin registry
-/// 3. Adds tool results as messages
-/// 4. Continues the conversation loop for the LLM to synthesize a response
+/// 1. Checks each call against the approval policy (`config.agent.approval`),
+///    asking the approval handler where the policy requires it
+/// 2. Adds the assistant message with tool calls to conversation history
+/// 3. Executes each approved tool via the plugin registry, running read-only
+///    tools concurrently (up to `config.agent.max_parallel_tool_calls`)
+/// 4. Adds tool results as messages; a denied or failed call (bad arguments,
+///    unknown tool, ...) is reported as a structured error result instead
+/// 5. Continues the conversation loop for the LLM to synthesize a response
 ///
 /// # Important Notes
 ///
 /// - Tool calls arrive in streaming chunks and must be preserved across chunks
-/// - The conversation loop continues until the LLM returns a non-tool response
+/// - The conversation loop continues until the LLM returns a non-tool response,
+///   or until one of the limits in `config.agent` is reached
 /// - All conversation history is maintained for context
 pub struct ChatManager {
     /// Nucleus core configuration
@@ -82,6 +104,12 @@ pub struct ChatManager {
     registry: Arc<PluginRegistry>,
     /// RAG manager for knowledge base integration (with persistent storage)
     rag_engine:  Arc<RagEngine>,
+    /// Persistent storage for chat sessions
+    sessions: SessionStore,
+    /// System prompt template, overriding `config.system_prompt`
+    system_prompt_override: Option<String>,
+    /// Decides on tool calls the approval policy marks as `ask`
+    approval_handler: Option<Arc<dyn ApprovalHandler>>,
 }
 
 impl ChatManager {
@@ -166,7 +194,10 @@ impl ChatManager {
     /// # }
     /// ```
     pub async fn with_provider(mut self, provider: Arc<dyn Provider>) -> Result<Self> {
-        self.rag_engine = Arc::new(RagEngine::new(&self.config, provider.clone()).await?);
+        // A separate embedding provider from `rag.provider` stays in place
+        if self.config.rag.provider.is_none() {
+            self.rag_engine = Arc::new(RagEngine::new(&self.config, provider.clone()).await?);
+        }
         self.provider = provider;
         Ok(self)
     }
@@ -264,9 +295,11 @@ impl ChatManager {
     ///
     /// Returns an error if:
     /// - The LLM request fails
-    /// - A requested tool fails to execute
     /// - The response cannot be parsed
     ///
+    /// Failed tool calls are reported to the LLM so it can retry; they end
+    /// the tool loop like the other limits in `agent`.
+    ///
     /// # Examples
     ///
     /// ```no_run
@@ -335,26 +368,409 @@ impl ChatManager {
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn query_stream<F>(&self, user_message: &str, mut on_chunk: F) -> Result<String>
+    pub async fn query_stream<F>(&self, user_message: &str, on_chunk: F) -> Result<String>
+    where
+        F: FnMut(&str) + Send,
+    {
+        Ok(self.query_detailed(user_message, on_chunk).await?.content)
+    }
+
+    /// Streaming query that also reports how the tool loop ended.
+    ///
+    /// The tool loop is bounded by the limits in `config.agent`. When one is
+    /// reached, the LLM is asked to answer with the information it has, and
+    /// [`QueryResult::limit_reached`] says which limit it was.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use nucleus_core::{ChatManager, Config};
+    /// # use nucleus_plugin::{PluginRegistry, Permission};
+    /// # async fn example() -> anyhow::Result<()> {
+    /// # let manager = ChatManager::new(Config::load_or_default(), PluginRegistry::new(Permission::READ_ONLY)).await?;
+    /// let result = manager.query_detailed("Refactor the parser", |_| {}).await?;
+    /// if let Some(limit) = result.limit_reached {
+    ///     println!("Stopped early: reached the {}", limit);
+    /// }
+    /// println!("{}", result.content);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn query_detailed<F>(&self, user_message: &str, on_chunk: F) -> Result<QueryResult>
+    where
+        F: FnMut(&str) + Send,
+    {
+        self.query_with_events(user_message, text_chunks(on_chunk)).await
+    }
+
+    /// Streaming query that stops when `cancel` is cancelled.
+    ///
+    /// Cancelling stops the response being generated and any running tools.
+    /// The query then returns what was generated so far, with
+    /// [`QueryResult::cancelled`] set.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use nucleus_core::{CancellationToken, ChatManager, Config};
+    /// # use nucleus_plugin::{PluginRegistry, Permission};
+    /// # use std::time::Duration;
+    /// # async fn example() -> anyhow::Result<()> {
+    /// # let manager = ChatManager::new(Config::load_or_default(), PluginRegistry::new(Permission::READ_ONLY)).await?;
+    /// let cancel = CancellationToken::new();
+    /// let timer = cancel.clone();
+    /// tokio::spawn(async move {
+    ///     tokio::time::sleep(Duration::from_secs(5)).await;
+    ///     timer.cancel();
+    /// });
+    ///
+    /// let result = manager.query_cancellable("Explain the codebase", &cancel, |chunk| print!("{}", chunk)).await?;
+    /// if result.cancelled {
+    ///     println!("\n[stopped]");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn query_cancellable<F>(
+        &self,
+        user_message: &str,
+        cancel: &CancellationToken,
+        on_chunk: F,
+    ) -> Result<QueryResult>
+    where
+        F: FnMut(&str) + Send,
+    {
+        self.query_with_events_cancellable(user_message, cancel, text_chunks(on_chunk))
+            .await
+    }
+
+    /// Sends a query and reports its progress as [`ChatEvent`]s.
+    ///
+    /// Besides the streamed text, events cover reasoning, retrieved knowledge
+    /// base context, each tool call from request to result, and token usage.
+    /// The last event is [`ChatEvent::Final`] with the same result that is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use nucleus_core::{ChatManager, Config};
+    /// # use nucleus_core::chat::ChatEvent;
+    /// # use nucleus_plugin::{PluginRegistry, Permission};
+    /// # async fn example() -> anyhow::Result<()> {
+    /// # let manager = ChatManager::new(Config::load_or_default(), PluginRegistry::new(Permission::READ_ONLY)).await?;
+    /// manager.query_with_events("What does main.rs do?", |event| match event {
+    ///     ChatEvent::TextDelta { text } => print!("{}", text),
+    ///     ChatEvent::ToolCallStarted { name, .. } => println!("[running {}]", name),
+    ///     ChatEvent::ToolCallFinished { name, duration, .. } => println!("[{} took {:?}]", name, duration),
+    ///     _ => {}
+    /// }).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn query_with_events<E>(&self, user_message: &str, on_event: E) -> Result<QueryResult>
+    where
+        E: FnMut(ChatEvent) + Send,
+    {
+        self.query_with_events_cancellable(user_message, &CancellationToken::new(), on_event)
+            .await
+    }
+
+    /// Version of [`query_with_events`](Self::query_with_events) that stops
+    /// when `cancel` is cancelled; see [`query_cancellable`](Self::query_cancellable).
+    pub async fn query_with_events_cancellable<E>(
+        &self,
+        user_message: &str,
+        cancel: &CancellationToken,
+        mut on_event: E,
+    ) -> Result<QueryResult>
+    where
+        E: FnMut(ChatEvent) + Send,
+    {
+        let mut messages = Vec::new();
+        let result = self
+            .run_turn(&mut messages, user_message, cancel, &mut on_event)
+            .await?;
+        on_event(ChatEvent::Final(result.clone()));
+        Ok(result)
+    }
+
+    /// Sends a query and returns its progress as a stream of [`ChatEvent`]s.
+    ///
+    /// The query runs while the stream is polled, and dropping the stream
+    /// abandons it. The stream ends with [`ChatEvent::Final`], or with
+    /// [`ChatEvent::Error`] if the query fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use nucleus_core::{ChatManager, Config};
+    /// # use nucleus_core::chat::ChatEvent;
+    /// # use nucleus_plugin::{PluginRegistry, Permission};
+    /// use futures::StreamExt;
+    ///
+    /// # async fn example() -> anyhow::Result<()> {
+    /// # let manager = ChatManager::new(Config::load_or_default(), PluginRegistry::new(Permission::READ_ONLY)).await?;
+    /// let mut events = manager.query_events("What does main.rs do?");
+    /// while let Some(event) = events.next().await {
+    ///     if let ChatEvent::TextDelta { text } = event {
+    ///         print!("{}", text);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn query_events<'a>(&'a self, user_message: &'a str) -> BoxStream<'a, ChatEvent> {
+        event_stream(move |on_event| self.query_with_events(user_message, on_event))
+    }
+
+    /// Builds the system prompt sent at the start of every conversation.
+    ///
+    /// Renders the prompt template (the builder override, or
+    /// `config.system_prompt`) and appends a section describing the
+    /// working directory and registered tools. See [`build_system_prompt`].
+    pub fn system_prompt(&self) -> String {
+        let template = self
+            .system_prompt_override
+            .as_deref()
+            .unwrap_or(&self.config.system_prompt);
+        let cwd = std::env::current_dir().unwrap_or_default();
+
+        build_system_prompt(template, &self.registry, &cwd)
+    }
+
+    /// Starts a new chat session.
+    ///
+    /// The session is saved once its first turn completes.
+    pub fn new_session(&self) -> ChatSession {
+        ChatSession::new()
+    }
+
+    /// Sends a query as the next turn of `session`.
+    ///
+    /// The LLM sees the session's previous messages, so follow-up questions
+    /// keep their context. After the turn completes, the new messages are
+    /// appended to the session and it is saved to `storage.chat_history_path`.
+    /// If the turn fails, the session is left unchanged.
+    ///
+    /// When the history outgrows the model's context window, older turns are
+    /// summarized or dropped as configured in `llm.context`, and the session
+    /// is saved in that shortened form.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use nucleus_core::{ChatManager, Config};
+    /// # use nucleus_plugin::{PluginRegistry, Permission};
+    /// # async fn example() -> anyhow::Result<()> {
+    /// # let manager = ChatManager::new(Config::load_or_default(), PluginRegistry::new(Permission::READ_ONLY)).await?;
+    /// let mut session = manager.new_session();
+    /// manager.query_session(&mut session, "What does src/main.rs do?").await?;
+    /// manager.query_session(&mut session, "How could it be simplified?").await?;
+    ///
+    /// // Later, possibly in another process
+    /// let mut session = manager.load_session(&session.id).await?;
+    /// manager.query_session(&mut session, "Write the simplified version").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn query_session(&self, session: &mut ChatSession, user_message: &str) -> Result<String> {
+        self.query_session_stream(session, user_message, |_| {}).await
+    }
+
+    /// Streaming version of [`query_session`](Self::query_session).
+    pub async fn query_session_stream<F>(
+        &self,
+        session: &mut ChatSession,
+        user_message: &str,
+        on_chunk: F,
+    ) -> Result<String>
+    where
+        F: FnMut(&str) + Send,
+    {
+        Ok(self.query_session_detailed(session, user_message, on_chunk).await?.content)
+    }
+
+    /// Session version of [`query_detailed`](Self::query_detailed).
+    pub async fn query_session_detailed<F>(
+        &self,
+        session: &mut ChatSession,
+        user_message: &str,
+        on_chunk: F,
+    ) -> Result<QueryResult>
     where
         F: FnMut(&str) + Send,
+    {
+        self.query_session_with_events(session, user_message, text_chunks(on_chunk))
+            .await
+    }
+
+    /// Session version of [`query_with_events`](Self::query_with_events).
+    ///
+    /// [`ChatEvent::Final`] is emitted once the session has been saved.
+    pub async fn query_session_with_events<E>(
+        &self,
+        session: &mut ChatSession,
+        user_message: &str,
+        on_event: E,
+    ) -> Result<QueryResult>
+    where
+        E: FnMut(ChatEvent) + Send,
+    {
+        self.query_session_with_events_cancellable(session, user_message, &CancellationToken::new(), on_event)
+            .await
+    }
+
+    /// Session version of [`query_with_events_cancellable`](Self::query_with_events_cancellable).
+    ///
+    /// A cancelled turn is kept in the session with the partial answer.
+    pub async fn query_session_with_events_cancellable<E>(
+        &self,
+        session: &mut ChatSession,
+        user_message: &str,
+        cancel: &CancellationToken,
+        mut on_event: E,
+    ) -> Result<QueryResult>
+    where
+        E: FnMut(ChatEvent) + Send,
+    {
+        let response = self
+            .run_turn(&mut session.messages, user_message, cancel, &mut on_event)
+            .await?;
+
+        session.touch();
+        self.sessions
+            .save(session)
+            .await
+            .context("Failed to save chat session")?;
+
+        on_event(ChatEvent::Final(response.clone()));
+        Ok(response)
+    }
+
+    /// Session version of [`query_events`](Self::query_events).
+    ///
+    /// If the turn fails, the stream ends with [`ChatEvent::Error`] and the
+    /// session is left unchanged.
+    pub fn query_session_events<'a>(
+        &'a self,
+        session: &'a mut ChatSession,
+        user_message: &'a str,
+    ) -> BoxStream<'a, ChatEvent> {
+        event_stream(move |on_event| self.query_session_with_events(session, user_message, on_event))
+    }
+
+    /// Lists saved chat sessions, most recently updated first.
+    pub async fn list_sessions(&self) -> Result<Vec<SessionSummary>> {
+        self.sessions.list().await
+    }
+
+    /// Loads a saved chat session to resume it.
+    pub async fn load_session(&self, id: &str) -> Result<ChatSession> {
+        self.sessions.load(id).await
+    }
+
+    /// Deletes a saved chat session.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the session existed.
+    pub async fn delete_session(&self, id: &str) -> Result<bool> {
+        self.sessions.delete(id).await
+    }
+
+    /// Runs one conversation turn on top of `history`.
+    ///
+    /// On success the user message (without RAG context) and every message
+    /// produced during the turn are appended to `history`, which may also
+    /// have been shortened to fit the context window. On failure, or when
+    /// the turn was cancelled before the model responded, `history` is left
+    /// unchanged.
+    async fn run_turn<E>(
+        &self,
+        history: &mut Vec<Message>,
+        user_message: &str,
+        cancel: &CancellationToken,
+        on_event: &mut E,
+    ) -> Result<QueryResult>
+    where
+        E: FnMut(ChatEvent) + Send,
+    {
+        let mut past = history.clone();
+        let mut turn = Vec::new();
+        let response = self
+            .run_turn_inner(&mut past, &mut turn, user_message, cancel, on_event)
+            .await?;
+
+        // Chat templates expect the roles to alternate, so the next user
+        // message must follow an assistant message
+        if response.cancelled {
+            match turn.last().map(|message| message.role.as_str()) {
+                Some("user") => return Ok(response),
+                Some("tool") => turn.push(Message::assistant(None, "")),
+                _ => {}
+            }
+        }
+
+        // Keep the plain question; the RAG context was only needed for this turn
+        turn[0].content = user_message.to_string();
+        for message in &mut turn {
+            message.context = None;
+        }
+        *history = past;
+        history.extend(turn);
+        Ok(response)
+    }
+
+    /// Runs the tool loop for one turn.
+    ///
+    /// `past` holds the earlier turns and `messages` collects the messages of
+    /// this turn. Both may be shortened to fit the context window.
+    ///
+    /// When `cancel` fires, the turn ends with what was generated so far. The
+    /// messages stay consistent: every recorded tool call gets a result.
+    async fn run_turn_inner<E>(
+        &self,
+        past: &mut Vec<Message>,
+        messages: &mut Vec<Message>,
+        user_message: &str,
+        cancel: &CancellationToken,
+        on_event: &mut E,
+    ) -> Result<QueryResult>
+    where
+        E: FnMut(ChatEvent) + Send,
     {
         // Retrieve relevant context from knowledge base if available
         let rag_count = self.rag_engine.count().await;
         debug!("RAG knowledge base has {} documents", rag_count);
         
-        // Context retrieved from RAG
-        let context = if rag_count > 0 {
+        // Knowledge base chunks relevant to the query
+        let results = if rag_count > 0 {
             debug!("Retrieving RAG context for query: {}", user_message);
-            self.rag_engine.retrieve_context(user_message).await
+            self.rag_engine.retrieve(user_message).await
                 .unwrap_or_else(|e| {
                     debug!("Could not retrieve RAG context: {}", e);
-                    String::new()
+                    Vec::new()
                 })
         } else {
             debug!("RAG knowledge base is empty, skipping context retrieval");
-            String::new()
+            Vec::new()
         };
+
+        if !results.is_empty() {
+            let sources = results
+                .iter()
+                .map(|result| ContextSource {
+                    source: result.document.metadata.get("source").cloned(),
+                    score: result.score,
+                    content: result.document.content.clone(),
+                })
+                .collect();
+            on_event(ChatEvent::ContextRetrieved { sources });
+        }
+
+        // Context retrieved from RAG
+        let context = RagEngine::format_context(&results);
         
         // Construct user message with context if available
         let enhanced_message = if !context.is_empty() {
@@ -365,91 +781,376 @@ impl ChatManager {
             user_message.to_string()
         };
         
-        let mut messages = vec![Message::user(Some(context.clone()), &enhanced_message)];
+        messages.push(Message::user(Some(context.clone()), &enhanced_message));
 
         let tools = self.build_tools();
+        // Regenerated every turn rather than stored in the history, so
+        // resumed sessions see the current date, directory and tools
+        let system_message = Message::system(None, self.system_prompt());
+        let context_window = ContextWindow::new(&self.config.llm);
+        let mut budget = AgentBudget::new(&self.config.agent);
+        // Stops requests and tool calls when the caller cancels or the deadline
+        // passes, so a slow response or tool can't hold up the final answer
+        let stop = cancel.child_token();
+        if let Some(remaining) = budget.remaining() {
+            cancel_after(&stop, remaining);
+        }
+        let _stop_guard = stop.clone().drop_guard();
+        let mut consecutive_failures = 0;
+        let mut tool_call_count = 0;
+        // Made-up tool call ids must not repeat those of earlier turns in the history
+        let turn_id = turn_id();
+        let mut usage = Usage::default();
 
-        loop {
-            let mut request = ChatRequest::new(&self.config.llm.model, messages.clone())
-                .with_temperature(self.config.llm.temperature);
+        let limit = loop {
+            if cancel.is_cancelled() {
+                return Ok(cancelled_result(String::new(), usage));
+            }
+            if let Some(limit) = budget.check_deadline() {
+                break limit;
+            }
+
+            // Tool results can push the conversation over the limit, so check before every request
+            context_window
+                .fit(self.provider.as_ref(), &self.config.llm.model, &system_message, &tools, past, messages)
+                .await;
+
+            let mut request = ChatRequest::new(&self.config.llm.model, request_messages(&system_message, past, messages))
+                .with_temperature(self.config.llm.temperature)
+                .with_sampling(self.config.llm.sampling.clone())
+                .with_thinking(self.config.llm.enable_thinking)
+                .with_cancellation(stop.clone());
 
             if !tools.is_empty() {
                 request.tools = Some(tools.clone());
             }
 
-            // Stream the LLM response, accumulating content and preserving tool calls.
-            // Important: Tool calls may arrive in early chunks while content streams,
-            // so we must preserve them separately from the final chunk.
-            let mut accumulated_content = String::new();
-            let mut current_response: Option<ChatResponse> = None;
-            let mut tool_calls: Option<Vec<ToolCall>> = None;
-            self.provider
-                .chat(request, Box::new(|response| {
-                    // Call user's streaming callback with incremental content
-                    if !response.done && !response.content.is_empty() {
-                        on_chunk(&response.content);
-                    }
-                    
-                    // Accumulate incremental content (response.content), not full message
-                    accumulated_content.push_str(&response.content);
-                    
-                    // Preserve tool calls from any chunk - they typically arrive early
-                    // in the stream and may be absent from the final done=true chunk
-                    if let Some(ref tool_calls_ref) = response.message.tool_calls {
-                        tool_calls = Some(tool_calls_ref.clone());
-                    }
-                    
-                    current_response = Some(response);
-                }))
-                .await
-                .context("Failed to get LLM response")?;
+            let mut assistant_message = self.complete(request, on_event, &mut usage).await?;
+            if stop.is_cancelled() {
+                // Keep the partial answer; tool calls of an interrupted response aren't run
+                messages.push(history_message(&context, &assistant_message));
+                if cancel.is_cancelled() {
+                    return Ok(cancelled_result(assistant_message.content, usage));
+                }
+                // The deadline passed, which the next iteration reports
+                continue;
+            }
 
-            let mut response = current_response
-                .context("No response from LLM")?;
+            // Handle tool calls: execute each tool and add results to conversation
+            if let Some(tool_calls) = &mut assistant_message.tool_calls {
+                let first_index = tool_call_count;
+                // Results refer to their call by id; make one up where the backend didn't
+                for (index, tool_call) in (first_index..).zip(tool_calls.iter_mut()) {
+                    tool_call.id.get_or_insert_with(|| format!("call_{}_{}", turn_id, index));
+                }
+                let tool_calls = &*tool_calls;
 
-            // Reconstruct the complete message with accumulated content and preserved tool calls
-            response.message.content = accumulated_content;
-            response.message.tool_calls = tool_calls;
-            let assistant_message = response.message;
+                for tool_call in tool_calls {
+                    on_event(ChatEvent::ToolCallRequested {
+                        index: tool_call_count,
+                        name: tool_call.function.name.clone(),
+                        arguments: tool_call.function.arguments.clone(),
+                    });
+                    tool_call_count += 1;
+                }
+
+                if let Err(limit) = budget.start_round(tool_calls.len()) {
+                    break limit;
+                }
+
+                // Settle approvals before anything runs, so edited arguments
+                // are what the history records
+                let mut reviewed = Vec::with_capacity(tool_calls.len());
+                for (index, tool_call) in (first_index..).zip(tool_calls) {
+                    let Some(decision) = stop
+                        .run_until_cancelled(review(
+                            &self.config.agent.approval,
+                            self.approval_handler.as_deref(),
+                            &self.registry,
+                            tool_call,
+                        ))
+                        .await
+                    else {
+                        break;
+                    };
+
+                    let mut tool_call = tool_call.clone();
+                    let denial = match decision {
+                        ApprovalDecision::Approve => None,
+                        ApprovalDecision::Edit { arguments } => {
+                            info!(tool_name = %tool_call.function.name, "Tool call arguments edited during approval");
+                            tool_call.function.arguments = arguments;
+                            None
+                        }
+                        ApprovalDecision::Deny { reason } => {
+                            info!(tool_name = %tool_call.function.name, reason = %reason, "Tool call denied");
+                            on_event(ChatEvent::ToolCallDenied {
+                                index,
+                                name: tool_call.function.name.clone(),
+                                reason: reason.clone(),
+                            });
+                            Some(reason)
+                        }
+                    };
+                    reviewed.push((tool_call, denial));
+                }
+                if stop.is_cancelled() {
+                    messages.push(history_message(&context, &assistant_message));
+                    if cancel.is_cancelled() {
+                        return Ok(cancelled_result(assistant_message.content, usage));
+                    }
+                    continue;
+                }
 
-            // Handle tool calls: execute each tool and add results to conversation
-            if let Some(tool_calls) = &assistant_message.tool_calls {
                 // Add the assistant's message with tool calls to conversation history
                 messages.push(Message {
                     role: "assistant".to_string(),
                     context: Some(context.to_string()),
                     content: assistant_message.content.clone(),
+                    reasoning: assistant_message.reasoning.clone(),
                     images: None,
-                    tool_calls: Some(tool_calls.clone()),
+                    tool_calls: Some(reviewed.iter().map(|(tool_call, _)| tool_call.clone()).collect()),
+                    tool_call_id: None,
+                    name: None,
                 });
 
-                // Execute each requested tool and add results
-                for tool_call in tool_calls {
-                    let tool_name = &tool_call.function.name;
-                    let tool_args = &tool_call.function.arguments;
-                    info!(tool_name = %tool_name, "Executing tool");
+                // Execute each approved tool and add results; denials are reported to the LLM.
+                // Read-only calls run concurrently, anything that writes or executes runs on
+                // its own, and results are added in the order the LLM requested them.
+                let parallel: Vec<bool> = reviewed
+                    .iter()
+                    .map(|(tool_call, denial)| denial.is_some() || is_read_only(&self.registry, &tool_call.function.name))
+                    .collect();
+                let concurrency = self.config.agent.max_parallel_tool_calls.max(1);
 
-                    let result = self
-                        .registry
-                        .execute(tool_name, tool_args.clone())
-                        .await
-                        .with_context(|| format!("Failed to execute tool: {}", tool_name))?;
-
-                    // Add tool result as a message for the LLM to synthesize
-                    messages.push(Message {
-                        role: "tool".to_string(),
-                        context: Some(context.to_string()),
-                        content: result.content,
-                        images: None,
-                        tool_calls: None,
-                    });
+                // A round fails when none of its calls succeed
+                let mut round_failed = false;
+                let mut round_succeeded = false;
+                for batch in parallel_batches(&parallel) {
+                    let outcomes: Vec<_> = {
+                        // Running calls report progress through the same callback
+                        let events = Mutex::new(&mut *on_event);
+                        stream::iter(batch.clone())
+                            .map(|i| self.run_tool_call(first_index + i, &reviewed[i].0, reviewed[i].1.is_some(), cancel, &stop, &events))
+                            .buffered(concurrency)
+                            .collect()
+                            .await
+                    };
+
+                    for (i, outcome) in batch.zip(outcomes) {
+                        let (tool_call, denial) = &reviewed[i];
+                        let content = match (denial, outcome) {
+                            (Some(reason), _) => ToolError::denied(reason.clone()).to_content(),
+                            (None, Some(Ok(output))) => {
+                                round_succeeded = true;
+                                output.content
+                            }
+                            (None, Some(Err(e))) => {
+                                // Failures go back to the model so it can correct the call
+                                round_failed = true;
+                                e.to_content()
+                            }
+                            // Skipped or stopped because the query was cancelled or ran out of time
+                            (None, None) => stopped_error(cancel).to_content(),
+                        };
+
+                        // Add tool result as a message for the LLM to synthesize
+                        messages.push(Message::tool_result(Some(context.to_string()), tool_call, content));
+                    }
+                }
+                if cancel.is_cancelled() {
+                    return Ok(cancelled_result(assistant_message.content, usage));
+                }
+
+                if round_succeeded {
+                    consecutive_failures = 0;
+                } else if round_failed {
+                    consecutive_failures += 1;
+                    let max_failures = self.config.agent.max_consecutive_tool_failures;
+                    if consecutive_failures >= max_failures {
+                        break AgentLimit::ToolFailures(max_failures);
+                    }
                 }
                 // Continue loop to get LLM's response using the tool results
             } else {
                 // No tool calls - this is the final response
-                return Ok(assistant_message.content);
+                messages.push(history_message(&context, &assistant_message));
+                return Ok(QueryResult {
+                    content: assistant_message.content,
+                    reasoning: assistant_message.reasoning,
+                    limit_reached: None,
+                    usage,
+                    cancelled: false,
+                });
             }
+        };
+
+        // A limit was reached: ask for an answer from what has been gathered, without tools
+        warn!(limit = %limit, "Tool loop limit reached, requesting a final answer");
+        context_window
+            .fit(self.provider.as_ref(), &self.config.llm.model, &system_message, &[], past, messages)
+            .await;
+
+        // Past the deadline, the final answer only gets a short grace period
+        let final_stop = cancel.child_token();
+        if let AgentLimit::Deadline(_) = limit {
+            cancel_after(&final_stop, FINAL_ANSWER_GRACE_PERIOD);
+        }
+        let _final_stop_guard = final_stop.clone().drop_guard();
+
+        let mut request_messages = request_messages(&system_message, past, messages);
+        request_messages.push(Message::user(None, final_answer_prompt(limit)));
+        let request = ChatRequest::new(&self.config.llm.model, request_messages)
+            .with_temperature(self.config.llm.temperature)
+            .with_sampling(self.config.llm.sampling.clone())
+            .with_thinking(self.config.llm.enable_thinking)
+            .with_cancellation(final_stop.clone());
+
+        let assistant_message = self.complete(request, on_event, &mut usage).await?;
+        messages.push(history_message(&context, &assistant_message));
+        Ok(QueryResult {
+            content: assistant_message.content,
+            reasoning: assistant_message.reasoning,
+            limit_reached: Some(limit),
+            usage,
+            cancelled: cancel.is_cancelled(),
+        })
+    }
+
+    /// Runs an approved tool call, reporting its progress.
+    ///
+    /// Returns `None` for a denied call, which isn't run, and for a call that
+    /// was skipped or stopped by `stop` because the query was cancelled or ran
+    /// out of time.
+    async fn run_tool_call<E>(
+        &self,
+        index: usize,
+        tool_call: &ToolCall,
+        denied: bool,
+        cancel: &CancellationToken,
+        stop: &CancellationToken,
+        events: &Mutex<&mut E>,
+    ) -> Option<std::result::Result<PluginOutput, ToolError>>
+    where
+        E: FnMut(ChatEvent) + Send,
+    {
+        if denied || stop.is_cancelled() {
+            return None;
+        }
+
+        let emit = |event| (events.lock().unwrap_or_else(|e| e.into_inner()))(event);
+        let tool_name = &tool_call.function.name;
+        info!(tool_name = %tool_name, "Executing tool");
+        emit(ChatEvent::ToolCallStarted {
+            index,
+            name: tool_name.clone(),
+            arguments: tool_call.function.arguments.clone(),
+        });
+
+        // Dropping the plugin's future on cancellation stops it
+        let started = Instant::now();
+        let Some(result) = stop.run_until_cancelled(execute_tool(&self.registry, tool_call)).await else {
+            info!(tool_name = %tool_name, "Tool call stopped");
+            emit(ChatEvent::ToolCallFailed {
+                index,
+                name: tool_name.clone(),
+                error: stopped_error(cancel).to_string(),
+                duration: started.elapsed(),
+            });
+            return None;
+        };
+        match &result {
+            Ok(output) => emit(ChatEvent::ToolCallFinished {
+                index,
+                name: tool_name.clone(),
+                output: output.content.clone(),
+                duration: started.elapsed(),
+            }),
+            Err(e) => {
+                warn!(tool_name = %tool_name, error = %e, "Tool call failed");
+                emit(ChatEvent::ToolCallFailed {
+                    index,
+                    name: tool_name.clone(),
+                    error: e.to_string(),
+                    duration: started.elapsed(),
+                });
+            }
+        }
+
+        Some(result)
+    }
+
+    /// Sends a request and collects the streamed response into one message.
+    ///
+    /// Emits the text deltas as they arrive, along with reasoning deltas if
+    /// `llm.reasoning.stream` is set, then the response's token usage, which
+    /// is also added to `usage`. The reasoning is kept on the message only if
+    /// `llm.reasoning.keep` is set. If the request is cancelled, the text
+    /// received until then is returned without tool calls.
+    async fn complete<E>(&self, request: ChatRequest, on_event: &mut E, usage: &mut Usage) -> Result<Message>
+    where
+        E: FnMut(ChatEvent) + Send,
+    {
+        // Stream the LLM response, accumulating content and preserving tool calls.
+        // Important: Tool calls may arrive in early chunks while content streams,
+        // so we must preserve them separately from the final chunk.
+        let reasoning_config = self.config.llm.reasoning;
+        let mut accumulated_content = String::new();
+        let mut accumulated_reasoning = String::new();
+        let mut current_response: Option<ChatResponse> = None;
+        let mut tool_calls: Option<Vec<ToolCall>> = None;
+        let mut response_usage = None;
+        let result = self.provider
+            .chat(request, Box::new(|response| {
+                // Forward incremental content as text and reasoning deltas
+                if reasoning_config.stream && !response.reasoning.is_empty() {
+                    on_event(ChatEvent::ReasoningDelta { text: response.reasoning.clone() });
+                }
+                if !response.content.is_empty() {
+                    on_event(ChatEvent::TextDelta { text: response.content.clone() });
+                }
+                
+                // Accumulate incremental content (response.content), not full message
+                accumulated_content.push_str(&response.content);
+                accumulated_reasoning.push_str(&response.reasoning);
+
+                if response.usage.is_some() {
+                    response_usage = response.usage;
+                }
+                
+                // Preserve tool calls from any chunk - they typically arrive early
+                // in the stream and may be absent from the final done=true chunk
+                if let Some(ref tool_calls_ref) = response.message.tool_calls {
+                    tool_calls = Some(tool_calls_ref.clone());
+                }
+                
+                current_response = Some(response);
+            }))
+            .await;
+        let cancelled = matches!(result, Err(ProviderError::Cancelled));
+        if !cancelled {
+            result.context("Failed to get LLM response")?;
+        }
+
+        let reasoning = (reasoning_config.keep && !accumulated_reasoning.is_empty()).then_some(accumulated_reasoning);
+        if let Some(response_usage) = response_usage {
+            *usage += response_usage;
+            on_event(ChatEvent::Usage(response_usage));
+        }
+        if cancelled {
+            let mut message = Message::assistant(None, accumulated_content);
+            message.reasoning = reasoning;
+            return Ok(message);
         }
+
+        let mut response = current_response
+            .context("No response from LLM")?;
+
+        // Reconstruct the complete message with accumulated content and preserved tool calls
+        response.message.content = accumulated_content;
+        response.message.reasoning = reasoning;
+        response.message.tool_calls = tool_calls;
+        Ok(response.message)
     }
 
     /// Converts registered plugins into Ollama tool definitions.
@@ -531,6 +1232,10 @@ pub struct ChatManagerBuilder {
     registry: PluginRegistry,
     llm_model_override: Option<String>,
     embedding_model_override: Option<EmbeddingModel>,
+    system_prompt_override: Option<String>,
+    approval_handler: Option<Arc<dyn ApprovalHandler>>,
+    provider_factory: ProviderFactory,
+    embedding_provider: Option<Arc<dyn Provider>>,
 }
 
 impl ChatManagerBuilder {
@@ -541,6 +1246,10 @@ impl ChatManagerBuilder {
             registry,
             llm_model_override: None,
             embedding_model_override: None,
+            system_prompt_override: None,
+            approval_handler: None,
+            provider_factory: ProviderFactory::new(),
+            embedding_provider: None,
         }
     }
 
@@ -617,10 +1326,83 @@ impl ChatManagerBuilder {
         self
     }
 
+    /// Override the system prompt template from config.
+    ///
+    /// The template may use `{{date}}`, `{{cwd}}` and `{{project}}`. The
+    /// environment and tools section is still appended.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use nucleus_core::{ChatManager, Config};
+    /// # use nucleus_plugin::{PluginRegistry, Permission};
+    /// # async fn example() -> anyhow::Result<()> {
+    /// # let config = Config::load_or_default();
+    /// # let registry = PluginRegistry::new(Permission::READ_ONLY);
+    /// let manager = ChatManager::builder(config, registry)
+    ///     .with_system_prompt("You are a code reviewer for {{project}}. Today is {{date}}.")
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.system_prompt_override = Some(prompt.into());
+        self
+    }
+
+    /// Override how reasoning is handled, from `config.llm.reasoning`.
+    ///
+    /// Whether the model reasons at all is set by `config.llm.enable_thinking`.
+    pub fn with_reasoning(mut self, reasoning: ReasoningConfig) -> Self {
+        self.config.llm.reasoning = reasoning;
+        self
+    }
+
+    /// Override the tool approval policy from `config.agent.approval`.
+    ///
+    /// Calls the policy marks as `ask` go to the handler set with
+    /// [`with_approval_handler`](Self::with_approval_handler), and are denied
+    /// if there is none.
+    pub fn with_approval_policy(mut self, policy: ApprovalPolicy) -> Self {
+        self.config.agent.approval = policy;
+        self
+    }
+
+    /// Set the handler that approves, denies or edits tool calls.
+    ///
+    /// See [`ApprovalHandler`] for an example.
+    pub fn with_approval_handler(mut self, handler: Arc<dyn ApprovalHandler>) -> Self {
+        self.approval_handler = Some(handler);
+        self
+    }
+
+    /// Set the factory that creates the provider selected by `llm.provider`.
+    ///
+    /// Needed to use custom providers; see [`ProviderFactory`] for an example.
+    pub fn with_provider_factory(mut self, factory: ProviderFactory) -> Self {
+        self.provider_factory = factory;
+        self
+    }
+
+    /// Set the provider that computes embeddings for the knowledge base.
+    ///
+    /// Without this, the provider selected by `rag.provider` is used, or the
+    /// chat provider if that isn't set.
+    pub fn with_embedding_provider(mut self, provider: Arc<dyn Provider>) -> Self {
+        self.embedding_provider = Some(provider);
+        self
+    }
+
     /// Builds the `ChatManager` with the configured settings.
     ///
-    /// This initializes the provider with the (possibly overridden) LLM model,
-    /// and the RAG system with the (possibly overridden) embedding model.
+    /// This connects to the MCP servers in `config.mcp.servers` and registers
+    /// their tools, letting them sample from the chat provider, then
+    /// initializes the provider selected by `llm.provider` with the (possibly
+    /// overridden) LLM model, and the RAG system with the (possibly
+    /// overridden) embedding model.
+    ///
+    /// MCP servers that fail to connect are logged and skipped.
     ///
     /// # Errors
     ///
@@ -637,17 +1419,95 @@ impl ChatManagerBuilder {
             config.rag.embedding_model = embedding_model;
         }
 
-        let registry = Arc::new(self.registry);
-        let provider: Arc<dyn Provider> = Arc::new(
-            MistralRsProvider::new(&config, Arc::clone(&registry)).await?
-        );
-        let rag_engine = Arc::new(RagEngine::new(&config, provider.clone()).await?);
+        let mut registry = self.registry;
+        let (sampling, sampling_provider) = ProviderSamplingHandler::deferred(&config);
+        register_configured_servers(&mut registry, &config, Some(Arc::new(sampling))).await;
+
+        let registry = Arc::new(registry);
+        let provider = self.provider_factory.create(&config, Arc::clone(&registry)).await?;
+        let _ = sampling_provider.set(Arc::clone(&provider));
+        let embedder = match self.embedding_provider {
+            Some(embedder) => embedder,
+            None => self
+                .provider_factory
+                .create_embedding(&config, Arc::clone(&registry))
+                .await?
+                .unwrap_or_else(|| provider.clone()),
+        };
+        let rag_engine = Arc::new(RagEngine::new(&config, embedder).await?);
+
+        let sessions = SessionStore::new(&config.storage.chat_history_path);
 
         Ok(ChatManager {
             config,
             provider,
             registry,
             rag_engine,
+            sessions,
+            system_prompt_override: self.system_prompt_override,
+            approval_handler: self.approval_handler,
         })
     }
 }
+
+/// Result of a query that was cancelled after producing `content`.
+fn cancelled_result(content: String, usage: Usage) -> QueryResult {
+    QueryResult {
+        content,
+        reasoning: None,
+        limit_reached: None,
+        usage,
+        cancelled: true,
+    }
+}
+
+/// Cancels `token` after `duration`.
+///
+/// The timer ends early once `token` is cancelled, so callers hold a drop
+/// guard for it to stop the timer when they're done.
+fn cancel_after(token: &CancellationToken, duration: Duration) {
+    let token = token.clone();
+    tokio::spawn(async move {
+        let _ = tokio::time::timeout(duration, token.cancelled()).await;
+        token.cancel();
+    });
+}
+
+/// Distinguishes turns started within the same millisecond
+static TURN_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Identifier for a turn, unique across sessions.
+fn turn_id() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let counter = TURN_COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{}-{}", millis, counter)
+}
+
+/// Error for a tool call that was stopped, by `cancel` or else by the deadline.
+fn stopped_error(cancel: &CancellationToken) -> ToolError {
+    if cancel.is_cancelled() {
+        ToolError::cancelled()
+    } else {
+        ToolError::timed_out()
+    }
+}
+
+/// An assistant response as recorded in the history, without tool calls.
+fn history_message(context: &str, response: &Message) -> Message {
+    let mut message = Message::assistant(Some(context.to_string()), &response.content);
+    message.reasoning = response.reasoning.clone();
+    message
+}
+
+/// Instructions for the final answer after the tool loop hit `limit`.
+fn final_answer_prompt(limit: AgentLimit) -> String {
+    format!(
+        "You have reached the {} for this request, so no more tools can be called. \
+         Answer the original question as well as you can with the information gathered so far, \
+         and say what is left unfinished.",
+        limit
+    )
+}
@@ -1,3 +1,15 @@
+mod approval;
+mod context;
+mod events;
+mod limits;
 mod manager;
+mod prompt;
+mod session;
+mod tools;
 
+pub use approval::{ApprovalDecision, ApprovalHandler, ApprovalRequest};
+pub use events::{ChatEvent, ContextSource};
+pub use limits::{AgentLimit, QueryResult};
 pub use manager::{ChatManager, ChatManagerBuilder};
+pub use prompt::build_system_prompt;
+pub use session::{ChatSession, SessionStore, SessionSummary};
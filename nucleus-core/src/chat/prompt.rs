@@ -0,0 +1,155 @@
+//! System prompt construction.
+//!
+//! The system prompt sent with every conversation is built from a template
+//! (`config.system_prompt` unless overridden) followed by an auto-generated
+//! section describing the environment and the available tools.
+//!
+//! # Template Variables
+//!
+//! - `{{date}}`: today's date (UTC), e.g. `2025-01-31`
+//! - `{{cwd}}`: the current working directory
+//! - `{{project}}`: the project name, taken from the enclosing git
+//!   repository's directory (or the working directory if there is none)
+
+use nucleus_plugin::PluginRegistry;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Builds the full system prompt for a conversation turn.
+///
+/// # Arguments
+///
+/// * `template` - Prompt template, possibly containing template variables
+/// * `registry` - Registry whose plugins are listed as available tools
+/// * `cwd` - Working directory reported to the model
+pub fn build_system_prompt(template: &str, registry: &PluginRegistry, cwd: &Path) -> String {
+    let date = today();
+    let project = project_name(cwd);
+    let cwd_display = cwd.display().to_string();
+
+    let mut prompt = template
+        .replace("{{date}}", &date)
+        .replace("{{cwd}}", &cwd_display)
+        .replace("{{project}}", &project)
+        .trim_end()
+        .to_string();
+
+    // Writing to a String never fails
+    let _ = write!(
+        prompt,
+        "\n\n## Environment\n- Working directory: {}\n- Project: {}\n- Date: {}\n",
+        cwd_display, project, date
+    );
+
+    let mut plugins = registry.all();
+    if !plugins.is_empty() {
+        plugins.sort_by(|a, b| a.name().cmp(b.name()));
+        prompt.push_str("\n## Tools\nYou can call the following tools when they help answer the user:\n");
+        for plugin in plugins {
+            let _ = writeln!(prompt, "- {}: {}", plugin.name(), plugin.description());
+        }
+    }
+
+    prompt
+}
+
+/// Name of the project containing `cwd`.
+fn project_name(cwd: &Path) -> String {
+    let root = cwd
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .unwrap_or(cwd);
+
+    root.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| root.display().to_string())
+}
+
+/// Today's date (UTC) as `YYYY-MM-DD`.
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) date.
+///
+/// Howard Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use nucleus_plugin::{Permission, Plugin, PluginOutput};
+    use serde_json::Value;
+    use std::sync::Arc;
+
+    struct NoopPlugin;
+
+    #[async_trait]
+    impl Plugin for NoopPlugin {
+        fn name(&self) -> &str {
+            "noop"
+        }
+
+        fn description(&self) -> &str {
+            "Does nothing"
+        }
+
+        fn parameter_schema(&self) -> Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        fn required_permission(&self) -> Permission {
+            Permission::READ_ONLY
+        }
+
+        async fn execute(&self, _input: Value) -> nucleus_plugin::Result<PluginOutput> {
+            Ok(PluginOutput::new(""))
+        }
+    }
+
+    #[test]
+    fn test_template_variables_and_sections() {
+        let mut registry = PluginRegistry::new(Permission::READ_ONLY);
+        registry.register(Arc::new(NoopPlugin));
+        let cwd = Path::new("/work/my-app");
+
+        let prompt = build_system_prompt("Helping with {{project}} in {{cwd}}.", &registry, cwd);
+
+        assert!(prompt.starts_with("Helping with my-app in /work/my-app."));
+        assert!(prompt.contains("- Working directory: /work/my-app"));
+        assert!(prompt.contains("- noop: Does nothing"));
+        assert!(!prompt.contains("{{"));
+    }
+
+    #[test]
+    fn test_no_tools_section_without_plugins() {
+        let registry = PluginRegistry::new(Permission::READ_ONLY);
+        let prompt = build_system_prompt("Hi", &registry, Path::new("/tmp"));
+        assert!(!prompt.contains("## Tools"));
+    }
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_723), (2024, 1, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+    }
+}
@@ -0,0 +1,271 @@
+//! Persistent multi-turn chat sessions.
+//!
+//! A [`ChatSession`] holds the message history of one conversation so that
+//! follow-up questions keep their context. Sessions are stored as one JSON
+//! file per session in the directory configured by
+//! `storage.chat_history_path`, and can be listed, resumed and deleted.
+
+use crate::provider::Message;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Maximum length of a title derived from the first user message
+const TITLE_MAX_CHARS: usize = 60;
+
+/// Distinguishes sessions created within the same millisecond
+static SESSION_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A conversation and its message history.
+///
+/// The history contains every user, assistant and tool message of the
+/// conversation, without the RAG context that was added to each query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatSession {
+    /// Unique identifier, also used as the file name
+    pub id: String,
+    /// Short description, taken from the first user message
+    pub title: Option<String>,
+    /// Creation time in seconds since the Unix epoch
+    pub created_at: u64,
+    /// Time of the last completed turn in seconds since the Unix epoch
+    pub updated_at: u64,
+    /// Message history, oldest first
+    pub messages: Vec<Message>,
+}
+
+impl ChatSession {
+    /// Creates an empty session with a fresh id.
+    pub fn new() -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let counter = SESSION_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+        Self {
+            id: format!("{}-{}", now.as_millis(), counter),
+            title: None,
+            created_at: now.as_secs(),
+            updated_at: now.as_secs(),
+            messages: Vec::new(),
+        }
+    }
+
+    /// Summary of the session for listings.
+    pub fn summary(&self) -> SessionSummary {
+        SessionSummary {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            message_count: self.messages.len(),
+        }
+    }
+
+    /// Records a completed turn, deriving the title from the first user message.
+    pub(crate) fn touch(&mut self) {
+        self.updated_at = unix_now();
+
+        if self.title.is_none() {
+            self.title = self
+                .messages
+                .iter()
+                .find(|message| message.role == "user")
+                .map(|message| title_from(&message.content));
+        }
+    }
+}
+
+impl Default for ChatSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Overview of a stored session, without its messages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSummary {
+    pub id: String,
+    pub title: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub message_count: usize,
+}
+
+/// Stores sessions as JSON files in a directory.
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    /// Creates a store backed by `dir`. The directory is created on first save.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Directory the sessions are stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Saves a session, replacing any previous version.
+    pub async fn save(&self, session: &ChatSession) -> Result<()> {
+        let path = self.path(&session.id)?;
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("Failed to create chat history directory {}", self.dir.display()))?;
+
+        // Write to a temporary file first so a crash can't leave a truncated session
+        let json = serde_json::to_vec_pretty(session).context("Failed to serialize session")?;
+        let tmp_path = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, json)
+            .await
+            .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+        tokio::fs::rename(&tmp_path, &path)
+            .await
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+
+        Ok(())
+    }
+
+    /// Loads a session by id.
+    pub async fn load(&self, id: &str) -> Result<ChatSession> {
+        let path = self.path(id)?;
+        let json = tokio::fs::read(&path)
+            .await
+            .with_context(|| format!("Session not found: {}", id))?;
+
+        serde_json::from_slice(&json).with_context(|| format!("Failed to parse session {}", id))
+    }
+
+    /// Lists stored sessions, most recently updated first.
+    ///
+    /// Files that can't be parsed are skipped.
+    pub async fn list(&self) -> Result<Vec<SessionSummary>> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("Failed to read chat history directory {}", self.dir.display())
+                })
+            }
+        };
+
+        let mut sessions = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+
+            let session = tokio::fs::read(&path)
+                .await
+                .ok()
+                .and_then(|json| serde_json::from_slice::<ChatSession>(&json).ok());
+            match session {
+                Some(session) => sessions.push(session.summary()),
+                None => tracing::warn!(path = %path.display(), "Skipping unreadable chat session"),
+            }
+        }
+
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.updated_at));
+        Ok(sessions)
+    }
+
+    /// Deletes a session.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the session existed.
+    pub async fn delete(&self, id: &str) -> Result<bool> {
+        let path = self.path(id)?;
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e).with_context(|| format!("Failed to delete session {}", id)),
+        }
+    }
+
+    /// File path for a session id, rejecting ids that could escape the directory.
+    fn path(&self, id: &str) -> Result<PathBuf> {
+        let valid = !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            anyhow::bail!("Invalid session id: {}", id);
+        }
+
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn title_from(content: &str) -> String {
+    let line = content.lines().next().unwrap_or_default().trim();
+    if line.chars().count() > TITLE_MAX_CHARS {
+        let truncated: String = line.chars().take(TITLE_MAX_CHARS).collect();
+        format!("{}...", truncated.trim_end())
+    } else {
+        line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_save_load_list_delete() {
+        let dir = TempDir::new().unwrap();
+        let store = SessionStore::new(dir.path().join("history"));
+        assert!(store.list().await.unwrap().is_empty());
+
+        let mut session = ChatSession::new();
+        session.messages.push(Message::user(None, "What does main.rs do?"));
+        session.messages.push(Message::assistant(None, "It starts the server."));
+        session.touch();
+        store.save(&session).await.unwrap();
+
+        let loaded = store.load(&session.id).await.unwrap();
+        assert_eq!(loaded.messages.len(), 2);
+        assert_eq!(loaded.title.as_deref(), Some("What does main.rs do?"));
+
+        let sessions = store.list().await.unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].message_count, 2);
+
+        assert!(store.delete(&session.id).await.unwrap());
+        assert!(!store.delete(&session.id).await.unwrap());
+        assert!(store.load(&session.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_id_rejected() {
+        let store = SessionStore::new("./history");
+        assert!(store.load("../config").await.is_err());
+    }
+
+    #[test]
+    fn test_session_ids_unique() {
+        assert_ne!(ChatSession::new().id, ChatSession::new().id);
+    }
+
+    #[test]
+    fn test_title_truncated() {
+        let title = title_from(&"word ".repeat(30));
+        assert!(title.ends_with("..."));
+        assert!(title.chars().count() <= TITLE_MAX_CHARS + 3);
+    }
+}
@@ -0,0 +1,236 @@
+//! Executing tool calls requested by the model.
+//!
+//! A failed tool call doesn't abort the query. The failure is turned into a
+//! [`ToolError`] and sent back to the model as the tool's result, so it can
+//! fix its arguments or try a different tool.
+//!
+//! Read-only tools from one response can run concurrently, while tools that
+//! write or execute run on their own; see [`parallel_batches`].
+
+use crate::provider::ToolCall;
+use nucleus_plugin::{PluginError, PluginOutput, PluginRegistry};
+use serde_json::{json, Value};
+use std::fmt;
+use std::ops::Range;
+
+/// A tool call that failed, reported to the model as its result.
+#[derive(Debug, Clone)]
+pub(crate) struct ToolError {
+    /// Machine-readable failure kind, e.g. `invalid_input`
+    kind: &'static str,
+    message: String,
+    /// Extra information to help the model correct the call
+    hint: Option<Value>,
+}
+
+impl ToolError {
+    /// The call was refused by the approval policy or the user.
+    pub(crate) fn denied(reason: impl Into<String>) -> Self {
+        Self {
+            kind: "denied",
+            message: reason.into(),
+            hint: Some(Value::String(
+                "The call was not run. Don't retry it unchanged; continue without it or ask the user.".to_string(),
+            )),
+        }
+    }
+
+    /// The query was cancelled before the call finished.
+    pub(crate) fn cancelled() -> Self {
+        Self {
+            kind: "cancelled",
+            message: "The query was cancelled before the tool finished".to_string(),
+            hint: None,
+        }
+    }
+
+    /// The query reached its time limit before the call finished.
+    pub(crate) fn timed_out() -> Self {
+        Self {
+            kind: "timed_out",
+            message: "The query's time limit was reached before the tool finished".to_string(),
+            hint: None,
+        }
+    }
+
+    fn unknown_tool(name: &str, registry: &PluginRegistry) -> Self {
+        let mut available: Vec<&str> = registry.all().iter().map(|plugin| plugin.name()).collect();
+        available.sort_unstable();
+
+        Self {
+            kind: "unknown_tool",
+            message: format!("No tool named '{}'", name),
+            hint: Some(json!({ "available_tools": available })),
+        }
+    }
+
+    fn from_plugin(error: PluginError, parameters: Value) -> Self {
+        let (kind, hint) = match &error {
+            // Show the schema so the model can fix its arguments
+            PluginError::InvalidInput(_) => ("invalid_input", Some(json!({ "parameters": parameters }))),
+            PluginError::ExecutionFailed(_) => ("execution_failed", None),
+            PluginError::PermissionDenied(_) => ("permission_denied", None),
+            PluginError::Other(_) => ("error", None),
+        };
+
+        Self {
+            kind,
+            message: error.to_string(),
+            hint,
+        }
+    }
+
+    /// Content of the tool-result message reporting this failure.
+    pub(crate) fn to_content(&self) -> String {
+        let mut error = json!({
+            "type": self.kind,
+            "message": self.message,
+        });
+        if let Some(hint) = &self.hint {
+            error["hint"] = hint.clone();
+        }
+
+        json!({ "error": error }).to_string()
+    }
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.kind)
+    }
+}
+
+/// Whether `name` is safe to run alongside other calls.
+///
+/// Unknown tools count as read-only since they fail without doing anything.
+pub(crate) fn is_read_only(registry: &PluginRegistry, name: &str) -> bool {
+    registry.get(name).is_none_or(|plugin| {
+        let permission = plugin.required_permission();
+        !permission.write && !permission.execute
+    })
+}
+
+/// Groups calls into batches that run one after another.
+///
+/// Consecutive calls that can run concurrently (`parallel[i]`) share a
+/// batch; every other call gets a batch of its own. Batches cover the calls
+/// in order, so results can be recorded in the order they were requested.
+pub(crate) fn parallel_batches(parallel: &[bool]) -> Vec<Range<usize>> {
+    let mut batches: Vec<Range<usize>> = Vec::new();
+    for (i, &concurrent) in parallel.iter().enumerate() {
+        match batches.last_mut() {
+            Some(batch) if concurrent && parallel[batch.start] => batch.end = i + 1,
+            _ => batches.push(i..i + 1),
+        }
+    }
+    batches
+}
+
+/// Executes a tool call through the registry.
+pub(crate) async fn execute_tool(
+    registry: &PluginRegistry,
+    tool_call: &ToolCall,
+) -> Result<PluginOutput, ToolError> {
+    let name = &tool_call.function.name;
+    let plugin = registry
+        .get(name)
+        .ok_or_else(|| ToolError::unknown_tool(name, registry))?;
+
+    plugin
+        .execute(tool_call.function.arguments.clone())
+        .await
+        .map_err(|e| ToolError::from_plugin(e, plugin.parameter_schema()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::ToolCallFunction;
+    use async_trait::async_trait;
+    use nucleus_plugin::{Permission, Plugin};
+    use std::sync::Arc;
+
+    /// Requires a string `path` argument
+    struct ReadPlugin;
+
+    #[async_trait]
+    impl Plugin for ReadPlugin {
+        fn name(&self) -> &str {
+            "read_file"
+        }
+
+        fn description(&self) -> &str {
+            "Reads a file"
+        }
+
+        fn parameter_schema(&self) -> Value {
+            json!({"type": "object", "properties": {"path": {"type": "string"}}})
+        }
+
+        fn required_permission(&self) -> Permission {
+            Permission::READ_ONLY
+        }
+
+        async fn execute(&self, input: Value) -> nucleus_plugin::Result<PluginOutput> {
+            input["path"]
+                .as_str()
+                .map(|path| PluginOutput::new(format!("contents of {}", path)))
+                .ok_or_else(|| PluginError::InvalidInput("Missing 'path'".to_string()))
+        }
+    }
+
+    fn registry() -> PluginRegistry {
+        let mut registry = PluginRegistry::new(Permission::READ_ONLY);
+        registry.register(Arc::new(ReadPlugin));
+        registry
+    }
+
+    fn call(name: &str, arguments: Value) -> ToolCall {
+        ToolCall {
+            id: None,
+            function: ToolCallFunction {
+                name: name.to_string(),
+                arguments,
+            },
+        }
+    }
+
+    #[test]
+    fn test_parallel_batches() {
+        assert_eq!(
+            parallel_batches(&[true, true, false, true, false, false, true]),
+            vec![0..2, 2..3, 3..4, 4..5, 5..6, 6..7]
+        );
+        assert_eq!(parallel_batches(&[true, true, true]), vec![0..3]);
+        assert!(parallel_batches(&[]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_successful_call() {
+        let output = execute_tool(&registry(), &call("read_file", json!({"path": "a.rs"})))
+            .await
+            .unwrap();
+        assert_eq!(output.content, "contents of a.rs");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_tool_lists_available_tools() {
+        let error = execute_tool(&registry(), &call("read", json!({}))).await.unwrap_err();
+        let content: Value = serde_json::from_str(&error.to_content()).unwrap();
+
+        assert_eq!(content["error"]["type"], "unknown_tool");
+        assert_eq!(content["error"]["hint"]["available_tools"], json!(["read_file"]));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_input_includes_schema() {
+        let error = execute_tool(&registry(), &call("read_file", json!({"file": "a.rs"})))
+            .await
+            .unwrap_err();
+        let content: Value = serde_json::from_str(&error.to_content()).unwrap();
+
+        assert_eq!(content["error"]["type"], "invalid_input");
+        assert_eq!(content["error"]["message"], "Invalid input: Missing 'path'");
+        assert!(content["error"]["hint"]["parameters"]["properties"]["path"].is_object());
+    }
+}
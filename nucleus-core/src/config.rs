@@ -1,9 +1,11 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use thiserror::Error;
 
 use crate::models::EmbeddingModel;
+use crate::provider::SamplingParams;
 
 #[derive(Debug, Error)]
 pub enum ConfigError {
@@ -21,11 +23,16 @@ pub type Result<T> = std::result::Result<T, ConfigError>;
 /// This includes the LLM model itself, as well as the features and customization you want it have
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// System prompt template; may use `{{date}}`, `{{cwd}}` and `{{project}}`
     pub system_prompt: String,
     pub llm: LlmConfig,
     pub rag: RagConfig,
     pub storage: StorageConfig,
     pub personalization: PersonalizationConfig,
+    #[serde(default)]
+    pub mcp: McpConfig,
+    #[serde(default)]
+    pub agent: AgentConfig,
 
     #[serde(skip)]
     pub permission: Permission,
@@ -58,10 +65,142 @@ impl Default for Permission {
 /// Configuration for the AI model
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LlmConfig {
+    /// Backend that runs the model: `mistralrs`, `ollama`,
+    /// `openai-compatible`, or the name of a custom provider. `None` (default)
+    /// uses mistral.rs, except in the [`Server`](crate::Server), which uses Ollama.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<ProviderKind>,
     pub model: String,
+    /// Server address for the `ollama` and `openai-compatible` providers,
+    /// e.g. `http://localhost:8080/v1` for llama.cpp's `llama-server`
     pub base_url: String,
+    /// Sent as a bearer token by the `openai-compatible` provider, for
+    /// servers that require one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
     pub temperature: f64,
+    /// Other sampling settings, such as `top_p`, `top_k` and `seed`, given
+    /// alongside `temperature`
+    #[serde(flatten)]
+    pub sampling: SamplingParams,
+    /// Whether thinking models such as Qwen3 reason before answering;
+    /// `None` (default) leaves it to the model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enable_thinking: Option<bool>,
+    /// What happens to the model's reasoning
+    #[serde(default)]
+    pub reasoning: ReasoningConfig,
     pub context_length: usize,
+    /// How conversations are kept within `context_length`
+    #[serde(default)]
+    pub context: ContextConfig,
+}
+
+/// Backend used to run the model.
+///
+/// Names other than the built-in ones select a custom provider registered
+/// with a [`ProviderFactory`](crate::provider::ProviderFactory).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum ProviderKind {
+    /// Run the model in-process with mistral.rs (default)
+    #[default]
+    MistralRs,
+    /// An Ollama server at `base_url`
+    Ollama,
+    /// A server speaking the OpenAI chat completions API at `base_url`,
+    /// such as llama.cpp, vLLM, LM Studio or LocalAI
+    OpenAiCompatible,
+    /// A provider registered under this name
+    Custom(String),
+}
+
+impl From<String> for ProviderKind {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "mistralrs" => Self::MistralRs,
+            "ollama" => Self::Ollama,
+            "openai-compatible" => Self::OpenAiCompatible,
+            _ => Self::Custom(name),
+        }
+    }
+}
+
+impl From<ProviderKind> for String {
+    fn from(kind: ProviderKind) -> Self {
+        match kind {
+            ProviderKind::MistralRs => "mistralrs".to_string(),
+            ProviderKind::Ollama => "ollama".to_string(),
+            ProviderKind::OpenAiCompatible => "openai-compatible".to_string(),
+            ProviderKind::Custom(name) => name,
+        }
+    }
+}
+
+/// Handling of the reasoning of thinking models.
+///
+/// Providers separate reasoning from the answer, so it never appears in a
+/// query's answer text. Setting both options to `false` discards it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReasoningConfig {
+    /// Pass reasoning on as it streams, as reasoning events or `<think>`
+    /// blocks in the text callback
+    pub stream: bool,
+
+    /// Keep reasoning with the assistant's messages in the session history
+    /// and the query result
+    pub keep: bool,
+}
+
+impl Default for ReasoningConfig {
+    fn default() -> Self {
+        Self {
+            stream: true,
+            keep: false,
+        }
+    }
+}
+
+/// Configuration for keeping conversations within the context window.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContextConfig {
+    /// Tokens kept free for the model's response
+    pub response_reserve: usize,
+
+    /// Tool results longer than this many tokens are truncated when the
+    /// conversation doesn't fit
+    pub max_tool_result_tokens: usize,
+
+    /// Strategies applied in order until the conversation fits
+    pub strategies: Vec<ContextStrategy>,
+}
+
+/// A way of shrinking a conversation that exceeds the context window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextStrategy {
+    /// Shorten large tool results, keeping their beginning
+    TruncateToolResults,
+    /// Replace older history with a summary written by the model
+    Summarize,
+    /// Remove the oldest turns of history
+    DropOldest,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            response_reserve: 2048,
+            max_tool_result_tokens: 4096,
+            strategies: vec![
+                ContextStrategy::TruncateToolResults,
+                ContextStrategy::Summarize,
+                ContextStrategy::DropOldest,
+            ],
+        }
+    }
 }
 
 /// Configuration for RAG processing.
@@ -72,6 +211,18 @@ pub struct RagConfig {
     pub embedding_model: EmbeddingModel,
     #[serde(default)]
     pub indexer: IndexerConfig,
+
+    /// Backend that computes embeddings; `None` (default) uses the chat provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<ProviderKind>,
+
+    /// Server address for the embedding provider; `None` uses `llm.base_url`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+
+    /// Bearer token for the embedding provider; `None` uses `llm.api_key`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
 }
 
 /// Configuration for file indexing behavior.
@@ -145,6 +296,9 @@ impl Default for RagConfig {
         Self {
             embedding_model,
             indexer,
+            provider: None,
+            base_url: None,
+            api_key: None,
         }
     }
 }
@@ -177,6 +331,198 @@ pub struct VectorDbConfig {
     pub collection_name: String,
 }
 
+/// Configuration for the tool-calling loop of a query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AgentConfig {
+    /// Failed tool calls are reported to the model so it can correct itself;
+    /// once this many rounds in a row have no successful call, the model is
+    /// asked to wrap up
+    pub max_consecutive_tool_failures: usize,
+
+    /// Maximum number of model responses that call tools in one query
+    pub max_tool_rounds: usize,
+
+    /// Maximum number of tool calls in one query
+    pub max_tool_calls: usize,
+
+    /// Time after which a running response or tool call is stopped and the
+    /// model gets a minute to wrap up; `None` means no limit
+    pub timeout_secs: Option<u64>,
+
+    /// How many read-only tool calls from one response may run at once;
+    /// tools that write or execute always run one at a time
+    pub max_parallel_tool_calls: usize,
+
+    /// Which tool calls run directly, need approval or are refused
+    pub approval: ApprovalPolicy,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            max_consecutive_tool_failures: 3,
+            max_tool_rounds: 20,
+            max_tool_calls: 50,
+            timeout_secs: Some(600),
+            max_parallel_tool_calls: 4,
+            approval: ApprovalPolicy::default(),
+        }
+    }
+}
+
+/// Rules deciding whether a tool call needs approval.
+///
+/// A rule for the tool's name takes precedence. Otherwise the strictest rule
+/// among the permissions the tool requires applies. The default allows every
+/// call, matching the behavior without a policy.
+///
+/// ```yaml
+/// agent:
+///   approval:
+///     read: allow
+///     write: ask
+///     execute: ask
+///     tools:
+///       exec: deny
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApprovalPolicy {
+    /// Rule for tools that read files or other data
+    pub read: ApprovalRule,
+    /// Rule for tools that modify files
+    pub write: ApprovalRule,
+    /// Rule for tools that run commands
+    pub execute: ApprovalRule,
+    /// Rules for individual tools by name
+    pub tools: HashMap<String, ApprovalRule>,
+}
+
+/// What happens when a tool is called.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApprovalRule {
+    /// Run the call without asking
+    #[default]
+    Allow,
+    /// Ask the approval handler first
+    Ask,
+    /// Refuse the call
+    Deny,
+}
+
+impl ApprovalPolicy {
+    /// Allow reads, ask before writing files or running commands.
+    pub fn confirm_changes() -> Self {
+        Self {
+            read: ApprovalRule::Allow,
+            write: ApprovalRule::Ask,
+            execute: ApprovalRule::Ask,
+            tools: HashMap::new(),
+        }
+    }
+
+    /// Set the rule for a single tool.
+    pub fn with_tool(mut self, name: impl Into<String>, rule: ApprovalRule) -> Self {
+        self.tools.insert(name.into(), rule);
+        self
+    }
+
+    /// The rule for calling `tool`, which requires `permission`.
+    pub fn rule_for(&self, tool: &str, permission: &nucleus_plugin::Permission) -> ApprovalRule {
+        if let Some(rule) = self.tools.get(tool) {
+            return *rule;
+        }
+
+        [
+            (permission.read, self.read),
+            (permission.write, self.write),
+            (permission.execute, self.execute),
+        ]
+        .into_iter()
+        .filter(|(required, _)| *required)
+        .map(|(_, rule)| rule)
+        .max()
+        .unwrap_or(ApprovalRule::Allow)
+    }
+}
+
+/// Configuration for MCP (Model Context Protocol) integration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct McpConfig {
+    /// Workspace directories exposed to MCP servers as roots
+    /// Empty list (default) means the current working directory
+    #[serde(default)]
+    pub roots: Vec<String>,
+
+    /// MCP servers to connect to; their tools are registered as plugins
+    #[serde(default)]
+    pub servers: Vec<McpServerConfig>,
+}
+
+/// An MCP server to spawn or connect to.
+///
+/// Tools are registered as `<name>__<tool>` so that servers exposing tools
+/// with the same name don't collide.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpServerConfig {
+    /// Server name, used as the tool namespace
+    pub name: String,
+
+    /// How to reach the server
+    #[serde(flatten)]
+    pub transport: McpTransportConfig,
+
+    /// Tools to register, by their name on the server
+    /// Empty list (default) means all tools
+    #[serde(default)]
+    pub tools: Vec<String>,
+
+    /// Timeout in seconds for connecting and for each request
+    #[serde(default = "default_mcp_timeout_secs")]
+    pub timeout_secs: u64,
+
+    /// Times a crashed stdio server is restarted in a row before giving up
+    /// 0 disables restarting
+    #[serde(default = "default_mcp_max_restarts")]
+    pub max_restarts: u32,
+}
+
+/// Transport used to reach an MCP server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "transport", rename_all = "lowercase")]
+pub enum McpTransportConfig {
+    /// Spawn a local process and talk over its stdin/stdout
+    Stdio {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default)]
+        env: HashMap<String, String>,
+    },
+    /// Streamable HTTP endpoint
+    Http {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+    /// WebSocket endpoint
+    WebSocket {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+}
+
+fn default_mcp_timeout_secs() -> u64 {
+    30
+}
+
+fn default_mcp_max_restarts() -> u32 {
+    5
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PersonalizationConfig {
     pub learn_from_interactions: bool,
@@ -216,10 +562,16 @@ impl Default for StorageConfig {
 impl Default for LlmConfig {
     fn default() -> Self {
         Self {
+            provider: None,
             model: "MaziyarPanahi/Qwen3-0.6B-GGUF:Qwen3-0.6B.Q4_K_M.gguf".to_string(), // Pre-quantized GGUF
             base_url: "http://localhost:11434".to_string(), // For Ollama provider (if used)
+            api_key: None,
             temperature: 0.6,
+            sampling: SamplingParams::default(),
+            enable_thinking: None,
+            reasoning: ReasoningConfig::default(),
             context_length: 32768,
+            context: ContextConfig::default(),
         }
     }
 }
@@ -234,6 +586,8 @@ impl Default for Config {
             rag: RagConfig::default(),
             storage: StorageConfig::default(),
             personalization: PersonalizationConfig::default(),
+            mcp: McpConfig::default(),
+            agent: AgentConfig::default(),
             permission: Permission::default(),
         }
     }
@@ -287,5 +641,142 @@ mod tests {
     fn test_rag_config_defaults() {
         let config = RagConfig::default();
         assert_eq!(config.embedding_model.name, EmbeddingModel::default().name);
+        assert_eq!(config.provider, None);
+    }
+
+    #[test]
+    fn test_approval_policy_rules() {
+        let policy = ApprovalPolicy::confirm_changes().with_tool("exec", ApprovalRule::Deny);
+
+        let read = nucleus_plugin::Permission::READ_ONLY;
+        let write = nucleus_plugin::Permission::READ_WRITE;
+        assert_eq!(policy.rule_for("read_file", &read), ApprovalRule::Allow);
+        assert_eq!(policy.rule_for("write_file", &write), ApprovalRule::Ask);
+        assert_eq!(policy.rule_for("exec", &read), ApprovalRule::Deny);
+        assert_eq!(ApprovalPolicy::default().rule_for("write_file", &write), ApprovalRule::Allow);
+    }
+
+    #[test]
+    fn test_provider_kind() {
+        let yaml = r#"
+provider: openai-compatible
+model: qwen3-8b
+base_url: http://localhost:8080/v1
+temperature: 0.6
+context_length: 32768
+"#;
+        let config: LlmConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.provider, Some(ProviderKind::OpenAiCompatible));
+        assert_eq!(config.api_key, None);
+
+        let custom = yaml.replace("openai-compatible", "my-backend");
+        let config: LlmConfig = serde_yaml::from_str(&custom).unwrap();
+        assert_eq!(config.provider, Some(ProviderKind::Custom("my-backend".to_string())));
+        assert!(serde_yaml::to_string(&config).unwrap().contains("provider: my-backend"));
+
+        let yaml = yaml.replace("provider: openai-compatible\n", "");
+        let config: LlmConfig = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(config.provider, None);
+    }
+
+    #[test]
+    fn test_sampling_params() {
+        let yaml = r#"
+model: qwen3:0.6b
+base_url: http://localhost:11434
+temperature: 0.6
+context_length: 32768
+top_k: 20
+top_p: 0.8
+min_p: 0
+repitition_penalty: 1.05
+stop: ["</answer>"]
+"#;
+        let config: LlmConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.sampling.top_k, Some(20));
+        assert_eq!(config.sampling.min_p, Some(0.0));
+        assert_eq!(config.sampling.repetition_penalty, Some(1.05));
+        assert_eq!(config.sampling.stop, vec!["</answer>"]);
+        assert_eq!(config.sampling.seed, None);
+    }
+
+    #[test]
+    fn test_reasoning_config() {
+        let yaml = r#"
+model: qwen3:0.6b
+base_url: http://localhost:11434
+temperature: 0.6
+context_length: 32768
+enable_thinking: false
+reasoning:
+  keep: true
+"#;
+        let config: LlmConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.enable_thinking, Some(false));
+        assert_eq!(config.reasoning, ReasoningConfig { stream: true, keep: true });
+        assert_eq!(LlmConfig::default().enable_thinking, None);
+    }
+
+    #[test]
+    fn test_partial_context_config() {
+        let yaml = r#"
+model: qwen3:0.6b
+base_url: http://localhost:11434
+temperature: 0.6
+context_length: 32768
+context:
+  strategies: [drop_oldest]
+"#;
+        let config: LlmConfig = serde_yaml::from_str(yaml).unwrap();
+        let defaults = ContextConfig::default();
+        assert_eq!(config.context.strategies, vec![ContextStrategy::DropOldest]);
+        assert_eq!(config.context.response_reserve, defaults.response_reserve);
+        assert_eq!(config.context.max_tool_result_tokens, defaults.max_tool_result_tokens);
+    }
+
+    #[test]
+    fn test_mcp_config_default() {
+        let config = McpConfig::default();
+        assert!(config.roots.is_empty());
+        assert!(config.servers.is_empty());
+    }
+
+    #[test]
+    fn test_mcp_servers_config() {
+        let yaml = r#"
+servers:
+  - name: github
+    transport: stdio
+    command: npx
+    args: ["-y", "@modelcontextprotocol/server-github"]
+    env:
+      GITHUB_TOKEN: secret
+    tools: [search_repositories]
+  - name: docs
+    transport: http
+    url: http://localhost:3000/mcp
+    headers:
+      Authorization: Bearer token
+    timeout_secs: 10
+"#;
+        let config: McpConfig = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.servers.len(), 2);
+
+        let github = &config.servers[0];
+        assert_eq!(github.timeout_secs, 30);
+        assert_eq!(github.max_restarts, 5);
+        assert_eq!(github.tools, vec!["search_repositories"]);
+        match &github.transport {
+            McpTransportConfig::Stdio { command, args, env } => {
+                assert_eq!(command, "npx");
+                assert_eq!(args.len(), 2);
+                assert_eq!(env["GITHUB_TOKEN"], "secret");
+            }
+            other => panic!("Expected stdio transport, got {:?}", other),
+        }
+
+        let docs = &config.servers[1];
+        assert_eq!(docs.timeout_secs, 10);
+        assert!(matches!(&docs.transport, McpTransportConfig::Http { headers, .. } if headers.len() == 1));
     }
 }
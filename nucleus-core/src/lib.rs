@@ -23,7 +23,7 @@ pub mod rag;
 pub mod server;
 
 // Public exports
-pub use chat::{ChatManager, ChatManagerBuilder};
+pub use chat::{AgentLimit, ChatEvent, ChatManager, ChatManagerBuilder, ChatSession, QueryResult};
 pub use config::{Config, IndexerConfig};
 pub use detection::{check_ollama_silent, detect_ollama, DetectionError, OllamaInfo};
 pub use rag::RagEngine;
@@ -35,5 +35,8 @@ pub use provider::{
     ToolFunction,
 };
 
+// Cancellation of in-progress queries
+pub use tokio_util::sync::CancellationToken;
+
 // MCP exports
-pub use mcp::McpClient;
+pub use mcp::{register_mcp_tools, McpClient, McpServer, McpToolPlugin};
@@ -1,92 +1,1062 @@
 //! MCP Client
 //!
 //! A client for communicating with MCP servers using various transports.
+//!
+//! # Concurrency
+//!
+//! The transport is owned by a background I/O task. Requests register a
+//! pending entry keyed by their id and wait on a oneshot channel, so any
+//! number of requests can be in flight at once from a shared `Arc<McpClient>`.
+//! Responses are routed back by id, and notifications from the server are
+//! broadcast to every [`subscribe`](McpClient::subscribe)r.
+//!
+//! # Timeouts and cancellation
+//!
+//! Requests time out after [`DEFAULT_REQUEST_TIMEOUT`] unless configured
+//! otherwise with [`with_request_timeout`](McpClient::with_request_timeout)
+//! or [`request_with_timeout`](McpClient::request_with_timeout). A request
+//! that times out, or whose future is dropped, is cancelled on the server
+//! with `notifications/cancelled`.
+//!
+//! # Server-initiated requests
+//!
+//! Servers may send `sampling/createMessage`, `roots/list` and
+//! `elicitation/create` requests. These are answered by the handlers
+//! registered with [`with_sampling_handler`](McpClient::with_sampling_handler),
+//! [`with_roots_handler`](McpClient::with_roots_handler) and
+//! [`with_elicitation_handler`](McpClient::with_elicitation_handler); any
+//! other request is rejected with "method not found".
 
+use crate::mcp::handler::{ElicitationHandler, RootsHandler, SamplingHandler};
+use crate::mcp::supervisor::{Connect, RestartPolicy, Supervisor};
+use crate::mcp::transport::http::HttpTransport;
 use crate::mcp::transport::stdio::StdioTransport;
-use crate::mcp::types::{JsonRpcMessage, JsonRpcRequest};
+use crate::mcp::transport::websocket::WebSocketTransport;
+use crate::mcp::transport::Transport;
+use crate::mcp::types::{
+    CallToolParams, CallToolResult, ClientCapabilities, CreateMessageParams, ElicitParams,
+    GetPromptParams, GetPromptResult, Implementation, InitializeParams, InitializeResult,
+    JsonRpcError, JsonRpcMessage, JsonRpcRequest, JsonRpcResponse, ListPromptsResult,
+    ListResourcesResult, ListRootsResult, ListToolsResult, McpTool, Notification, Prompt,
+    ReadResourceResult, Resource, ResourceContents, ResourceParams, ResultOrError,
+    PROTOCOL_VERSION,
+};
 use anyhow::{Context, Result};
 use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tracing::{debug, warn};
+
+/// Number of notifications buffered per subscriber before it starts lagging
+const NOTIFICATION_CAPACITY: usize = 64;
+
+/// Time to wait for a response unless overridden per client or per request
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A request received no response within its timeout
+///
+/// Returned inside the [`anyhow::Error`] of a request; use
+/// `error.downcast_ref::<RequestTimeout>()` to tell timeouts apart from
+/// other failures. The server has been sent `notifications/cancelled`.
+#[derive(Debug, Error)]
+#[error("MCP request '{method}' timed out after {timeout:?}")]
+pub struct RequestTimeout {
+    pub method: String,
+    pub timeout: Duration,
+}
+
+/// Commands sent from the client to its I/O task
+enum Command {
+    Send(JsonRpcMessage),
+    Close(oneshot::Sender<Result<()>>),
+}
+
+/// Requests waiting for a response, keyed by request id
+type PendingMap = HashMap<u64, oneshot::Sender<Result<Value>>>;
+
+/// Handlers for server-initiated requests
+#[derive(Default, Clone)]
+struct Handlers {
+    sampling: Option<Arc<dyn SamplingHandler>>,
+    roots: Option<Arc<dyn RootsHandler>>,
+    elicitation: Option<Arc<dyn ElicitationHandler>>,
+}
+
+/// State shared between the client and its I/O task
+struct Shared {
+    pending: Mutex<PendingMap>,
+    notifications: broadcast::Sender<Notification>,
+    handlers: RwLock<Handlers>,
+    alive: AtomicBool,
+}
 
 /// MCP Client
+///
+/// Works over any [`Transport`]: local server processes (stdio), remote
+/// servers (HTTP) and long-lived daemons (WebSocket) share the same API.
+///
+/// Creating a client spawns its I/O task, so it must be done from within a
+/// Tokio runtime. Dropping the client closes the transport.
 pub struct McpClient {
-    transport: StdioTransport,
-    next_id: u64,
+    commands: mpsc::UnboundedSender<Command>,
+    shared: Arc<Shared>,
+    next_id: AtomicU64,
+    server_info: RwLock<Option<InitializeResult>>,
+    request_timeout: Option<Duration>,
 }
 
 impl McpClient {
+    /// Create a new MCP client over the given transport
+    pub fn new(transport: impl Transport + 'static) -> Self {
+        Self::spawn(Box::new(transport), None)
+    }
+
+    /// Create a client that reconnects when the server goes away
+    ///
+    /// `connect` creates the transport, both initially and after each failure.
+    /// After a restart the `initialize` handshake is replayed automatically;
+    /// requests that were in flight when the server died fail.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use nucleus_core::mcp::{McpClient, RestartPolicy, StdioTransport};
+    ///
+    /// # async fn example() -> anyhow::Result<()> {
+    /// let client = McpClient::new_supervised(
+    ///     || async { StdioTransport::spawn("my-mcp-server", &[]) },
+    ///     RestartPolicy::default(),
+    /// )
+    /// .await?;
+    /// client.initialize().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn new_supervised<F, Fut, T>(connect: F, policy: RestartPolicy) -> Result<Self>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+        T: Transport + 'static,
+    {
+        let connect: Connect = Box::new(move || {
+            let transport = connect();
+            Box::pin(async move { Ok(Box::new(transport.await?) as Box<dyn Transport>) })
+        });
+        let supervisor = Supervisor::new(connect, policy);
+        let transport = supervisor.connect().await?;
+
+        Ok(Self::spawn(transport, Some(supervisor)))
+    }
+
+    /// Start the I/O task for `transport`
+    fn spawn(transport: Box<dyn Transport>, supervisor: Option<Supervisor>) -> Self {
+        let (commands, command_rx) = mpsc::unbounded_channel();
+        let (notifications, _) = broadcast::channel(NOTIFICATION_CAPACITY);
+        let shared = Arc::new(Shared {
+            pending: Mutex::new(HashMap::new()),
+            notifications,
+            handlers: RwLock::new(Handlers::default()),
+            alive: AtomicBool::new(true),
+        });
+
+        tokio::spawn(run_io(transport, command_rx, Arc::clone(&shared), supervisor));
+
+        Self {
+            commands,
+            shared,
+            next_id: AtomicU64::new(1),
+            server_info: RwLock::new(None),
+            request_timeout: Some(DEFAULT_REQUEST_TIMEOUT),
+        }
+    }
+
     /// Create a new MCP client with stdio transport
     pub fn new_stdio(command: &str, args: &[&str]) -> Result<Self> {
         let transport = StdioTransport::spawn(command, args)
             .context("Failed to create stdio transport")?;
 
-        Ok(Self {
-            transport,
-            next_id: 1,
+        Ok(Self::new(transport))
+    }
+
+    /// Create a new MCP client with HTTP transport
+    pub fn new_http(server_url: impl Into<String>) -> Self {
+        Self::new(HttpTransport::new(server_url))
+    }
+
+    /// Create a new MCP client with WebSocket transport
+    pub async fn new_websocket(url: &str) -> Result<Self> {
+        let transport = WebSocketTransport::connect(url)
+            .await
+            .context("Failed to create WebSocket transport")?;
+
+        Ok(Self::new(transport))
+    }
+
+    /// Set the timeout for requests that don't specify their own
+    ///
+    /// `None` waits forever. Defaults to [`DEFAULT_REQUEST_TIMEOUT`].
+    pub fn with_request_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Answer `sampling/createMessage` requests with `handler`
+    ///
+    /// Must be set before [`initialize`](Self::initialize) for the capability
+    /// to be advertised.
+    pub fn with_sampling_handler(self, handler: Arc<dyn SamplingHandler>) -> Self {
+        self.shared.handlers.write().unwrap().sampling = Some(handler);
+        self
+    }
+
+    /// Answer `roots/list` requests with `handler`
+    ///
+    /// Must be set before [`initialize`](Self::initialize) for the capability
+    /// to be advertised.
+    pub fn with_roots_handler(self, handler: Arc<dyn RootsHandler>) -> Self {
+        self.shared.handlers.write().unwrap().roots = Some(handler);
+        self
+    }
+
+    /// Answer `elicitation/create` requests with `handler`
+    ///
+    /// Must be set before [`initialize`](Self::initialize) for the capability
+    /// to be advertised.
+    pub fn with_elicitation_handler(self, handler: Arc<dyn ElicitationHandler>) -> Self {
+        self.shared.handlers.write().unwrap().elicitation = Some(handler);
+        self
+    }
+
+    /// Perform the MCP `initialize` handshake
+    ///
+    /// Sends `initialize`, stores the server's reply and then sends the
+    /// `notifications/initialized` notification. Must be called before any
+    /// other MCP request.
+    pub async fn initialize(&self) -> Result<InitializeResult> {
+        let capabilities = {
+            let handlers = self.shared.handlers.read().unwrap();
+            ClientCapabilities {
+                roots: handlers.roots.as_ref().map(|_| serde_json::json!({ "listChanged": false })),
+                sampling: handlers.sampling.as_ref().map(|_| serde_json::json!({})),
+                elicitation: handlers.elicitation.as_ref().map(|_| serde_json::json!({})),
+            }
+        };
+        let params = InitializeParams {
+            protocol_version: PROTOCOL_VERSION.to_string(),
+            capabilities,
+            client_info: Implementation::default(),
+        };
+
+        let result = self
+            .request("initialize", Some(serde_json::to_value(params)?))
+            .await
+            .context("MCP initialize request failed")?;
+        let result: InitializeResult =
+            serde_json::from_value(result).context("Invalid initialize result")?;
+
+        self.notify("notifications/initialized", None).await?;
+        *self.server_info.write().unwrap() = Some(result.clone());
+
+        Ok(result)
+    }
+
+    /// Get the server's `initialize` result, if the handshake has completed
+    pub fn server_info(&self) -> Option<InitializeResult> {
+        self.server_info.read().unwrap().clone()
+    }
+
+    /// List all tools exposed by the server, following pagination cursors
+    pub async fn list_tools(&self) -> Result<Vec<McpTool>> {
+        self.list_paginated("tools/list", |page: ListToolsResult| {
+            (page.tools, page.next_cursor)
+        })
+        .await
+    }
+
+    /// Call a tool on the server
+    ///
+    /// Tool-level failures are reported through [`CallToolResult::is_error`];
+    /// protocol-level failures (unknown tool, invalid arguments) are returned
+    /// as a [`JsonRpcError`] inside the error.
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<CallToolResult> {
+        self.call_tool_with_timeout(name, arguments, self.request_timeout)
+            .await
+    }
+
+    /// Call a tool with its own timeout instead of the client default
+    pub async fn call_tool_with_timeout(
+        &self,
+        name: &str,
+        arguments: Value,
+        timeout: Option<Duration>,
+    ) -> Result<CallToolResult> {
+        let params = CallToolParams {
+            name: name.to_string(),
+            arguments: Some(arguments),
+        };
+
+        let result = self
+            .request_with_timeout("tools/call", Some(serde_json::to_value(params)?), timeout)
+            .await?;
+
+        serde_json::from_value(result).context("Invalid tools/call result")
+    }
+
+    /// List all resources exposed by the server, following pagination cursors
+    pub async fn list_resources(&self) -> Result<Vec<Resource>> {
+        self.list_paginated("resources/list", |page: ListResourcesResult| {
+            (page.resources, page.next_cursor)
         })
+        .await
+    }
+
+    /// Read the contents of a resource
+    ///
+    /// A single URI may yield several contents, e.g. the files of a directory.
+    pub async fn read_resource(&self, uri: &str) -> Result<Vec<ResourceContents>> {
+        let params = ResourceParams {
+            uri: uri.to_string(),
+        };
+        let result = self
+            .request("resources/read", Some(serde_json::to_value(params)?))
+            .await?;
+        let result: ReadResourceResult =
+            serde_json::from_value(result).context("Invalid resources/read result")?;
+
+        Ok(result.contents)
+    }
+
+    /// Subscribe to changes of a resource
+    ///
+    /// The server then sends `notifications/resources/updated` with the URI
+    /// whenever the resource changes; receive them via [`subscribe`](Self::subscribe).
+    pub async fn subscribe_resource(&self, uri: &str) -> Result<()> {
+        let params = ResourceParams {
+            uri: uri.to_string(),
+        };
+        self.request("resources/subscribe", Some(serde_json::to_value(params)?))
+            .await?;
+        Ok(())
     }
 
-    /// Send a request and wait for a response
-    pub async fn request(
-        &mut self,
+    /// Stop receiving change notifications for a resource
+    pub async fn unsubscribe_resource(&self, uri: &str) -> Result<()> {
+        let params = ResourceParams {
+            uri: uri.to_string(),
+        };
+        self.request("resources/unsubscribe", Some(serde_json::to_value(params)?))
+            .await?;
+        Ok(())
+    }
+
+    /// List all prompts exposed by the server, following pagination cursors
+    pub async fn list_prompts(&self) -> Result<Vec<Prompt>> {
+        self.list_paginated("prompts/list", |page: ListPromptsResult| {
+            (page.prompts, page.next_cursor)
+        })
+        .await
+    }
+
+    /// Get a prompt, with its template filled in from `arguments`
+    pub async fn get_prompt(
+        &self,
+        name: &str,
+        arguments: HashMap<String, String>,
+    ) -> Result<GetPromptResult> {
+        let params = GetPromptParams {
+            name: name.to_string(),
+            arguments: (!arguments.is_empty()).then_some(arguments),
+        };
+        let result = self
+            .request("prompts/get", Some(serde_json::to_value(params)?))
+            .await?;
+
+        serde_json::from_value(result).context("Invalid prompts/get result")
+    }
+
+    /// Request every page of a paginated list method
+    ///
+    /// `split` extracts the items and next cursor from each page.
+    async fn list_paginated<P, T>(
+        &self,
+        method: &str,
+        split: impl Fn(P) -> (Vec<T>, Option<String>),
+    ) -> Result<Vec<T>>
+    where
+        P: serde::de::DeserializeOwned,
+    {
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let params = cursor.map(|c| serde_json::json!({ "cursor": c }));
+            let result = self.request(method, params).await?;
+            let page: P = serde_json::from_value(result)
+                .with_context(|| format!("Invalid {} result", method))?;
+
+            let (page_items, next_cursor) = split(page);
+            items.extend(page_items);
+            match next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// Send a request and wait for a response, using the client's timeout
+    ///
+    /// Other requests may be sent while this one is outstanding.
+    pub async fn request(&self, method: impl Into<String>, params: Option<Value>) -> Result<Value> {
+        self.request_with_timeout(method, params, self.request_timeout)
+            .await
+    }
+
+    /// Send a request and wait up to `timeout` for a response
+    ///
+    /// On timeout the server is sent `notifications/cancelled` and a
+    /// [`RequestTimeout`] error is returned. Dropping the returned future
+    /// before it completes cancels the request the same way.
+    pub async fn request_with_timeout(
+        &self,
         method: impl Into<String>,
         params: Option<Value>,
+        timeout: Option<Duration>,
     ) -> Result<Value> {
-        let id = Value::Number(serde_json::Number::from(self.next_id));
-        self.next_id += 1;
+        let method = method.into();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.shared.pending.lock().unwrap().insert(id, tx);
 
-        let request = JsonRpcRequest::new(id.clone(), method, params);
-        let message = JsonRpcMessage::Request(request);
+        // Cancels the request unless a response arrives first
+        let mut guard = PendingRequest {
+            client: self,
+            id,
+            method: &method,
+            done: false,
+        };
 
-        // Send the request
-        self.transport.send(&message).await?;
+        let request = JsonRpcRequest::new(Value::from(id), method.clone(), params);
+        if let Err(e) = self.send(JsonRpcMessage::Request(request)) {
+            guard.done = true;
+            self.shared.pending.lock().unwrap().remove(&id);
+            return Err(e);
+        }
 
-        // Wait for the response, matching by ID
-        loop {
-            let response = self.transport.receive().await?;
-
-            match response {
-                JsonRpcMessage::Response(resp) => {
-                    // Check if this is the response we're waiting for
-                    if resp.id == id {
-                        match resp.result_or_error {
-                            crate::mcp::types::ResultOrError::Success { result } => return Ok(result),
-                            crate::mcp::types::ResultOrError::Error { error } => {
-                                anyhow::bail!("JSON-RPC error: {} (code: {})", error.message, error.code)
-                            }
+        let response = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, rx).await {
+                Ok(response) => response,
+                Err(_) => {
+                    guard.cancel("Request timed out");
+                    return Err(RequestTimeout { method: method.clone(), timeout }.into());
+                }
+            },
+            None => rx.await,
+        };
+        guard.done = true;
+
+        response.context("MCP connection closed before a response was received")?
+    }
+
+    /// Send a notification (no response expected)
+    pub async fn notify(&self, method: impl Into<String>, params: Option<Value>) -> Result<()> {
+        let notification = JsonRpcRequest::notification(method, params);
+        self.send(JsonRpcMessage::Request(notification))
+    }
+
+    /// Subscribe to notifications sent by the server
+    ///
+    /// Examples include `notifications/tools/list_changed` and
+    /// `notifications/progress`. Only notifications received after
+    /// subscribing are delivered.
+    pub fn subscribe(&self) -> broadcast::Receiver<Notification> {
+        self.shared.notifications.subscribe()
+    }
+
+    /// Check if the transport is still alive
+    pub fn is_alive(&self) -> bool {
+        self.shared.alive.load(Ordering::Acquire)
+    }
+
+    /// Close the underlying transport
+    ///
+    /// Outstanding requests fail once the transport is closed.
+    pub async fn close(&self) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        if self.commands.send(Command::Close(tx)).is_err() {
+            // The I/O task has already exited
+            return Ok(());
+        }
+        rx.await.unwrap_or(Ok(()))
+    }
+
+    /// Queue a message for the I/O task
+    fn send(&self, message: JsonRpcMessage) -> Result<()> {
+        self.commands
+            .send(Command::Send(message))
+            .map_err(|_| anyhow::anyhow!("MCP connection is closed"))
+    }
+}
+
+/// An outstanding request, cancelled if dropped before it completes
+struct PendingRequest<'a> {
+    client: &'a McpClient,
+    id: u64,
+    method: &'a str,
+    done: bool,
+}
+
+impl PendingRequest<'_> {
+    /// Forget the request and tell the server to stop working on it
+    fn cancel(&mut self, reason: &str) {
+        self.done = true;
+        self.client.shared.pending.lock().unwrap().remove(&self.id);
+
+        // The spec forbids cancelling the handshake
+        if self.method == "initialize" {
+            return;
+        }
+        debug!(id = self.id, method = %self.method, reason, "Cancelling MCP request");
+        let notification = JsonRpcRequest::notification(
+            "notifications/cancelled",
+            Some(serde_json::json!({ "requestId": self.id, "reason": reason })),
+        );
+        // Nothing to cancel if the connection is already gone
+        let _ = self.client.send(JsonRpcMessage::Request(notification));
+    }
+}
+
+impl Drop for PendingRequest<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.cancel("Request cancelled by client");
+        }
+    }
+}
+
+/// Background task owning the transport
+///
+/// Writes queued messages and routes everything the server sends until the
+/// transport fails, the client is closed or the client is dropped. With a
+/// supervisor, a failed transport is replaced instead.
+async fn run_io(
+    mut transport: Box<dyn Transport>,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    shared: Arc<Shared>,
+    mut supervisor: Option<Supervisor>,
+) {
+    // Replies to server-initiated requests, produced by handler tasks
+    let (replies_tx, mut replies) = mpsc::unbounded_channel::<JsonRpcResponse>();
+
+    loop {
+        tokio::select! {
+            command = commands.recv() => match command {
+                Some(Command::Send(message)) => {
+                    if let Some(supervisor) = supervisor.as_mut() {
+                        supervisor.observe(&message);
+                    }
+                    if let Err(e) = transport.send(&message).await {
+                        warn!(error = %e, "Failed to send MCP message");
+                        if let JsonRpcMessage::Request(JsonRpcRequest::Request { id, .. }) = &message {
+                            shared.complete(id, Err(e));
                         }
-                    } else {
-                        // This is a response for a different request
-                        // In a full implementation, we'd route it to the correct handler
-                        // For now, we'll continue waiting
-                        continue;
                     }
                 }
-                JsonRpcMessage::Request(_) => {
-                    // Received a request from the server (server-initiated)
-                    // In a full implementation, we'd handle this
-                    // For now, we'll continue waiting for our response
-                    continue;
+                Some(Command::Close(done)) => {
+                    let _ = done.send(transport.close().await);
+                    break;
                 }
-            }
+                None => {
+                    // Client dropped
+                    let _ = transport.close().await;
+                    break;
+                }
+            },
+            Some(reply) = replies.recv() => {
+                if let Err(e) = transport.send(&JsonRpcMessage::Response(reply)).await {
+                    warn!(error = %e, "Failed to reply to server request");
+                }
+            },
+            incoming = transport.receive() => match incoming {
+                Ok(message) => shared.dispatch(message, &replies_tx),
+                Err(e) => {
+                    let Some(supervisor) = supervisor.as_mut() else {
+                        debug!(error = %e, "MCP transport closed");
+                        break;
+                    };
+
+                    warn!(error = %e, alive = transport.is_alive(), "MCP server connection lost");
+                    let _ = transport.close().await;
+                    shared.fail_pending("MCP server connection lost, restarting");
+                    match supervisor.restart().await {
+                        Some(restarted) => transport = restarted,
+                        None => break,
+                    }
+                }
+            },
         }
     }
 
-    /// Send a notification (no response expected)
-    pub async fn notify(&mut self, method: impl Into<String>, params: Option<Value>) -> Result<()> {
-        let notification = JsonRpcRequest::notification(method, params);
-        let message = JsonRpcMessage::Request(notification);
-        self.transport.send(&message).await?;
-        Ok(())
+    shared.alive.store(false, Ordering::Release);
+    // Dropping the senders wakes every waiter with a "connection closed" error
+    shared.pending.lock().unwrap().clear();
+}
+
+impl Shared {
+    /// Fail every outstanding request with `reason`
+    fn fail_pending(&self, reason: &str) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        for (_, sender) in pending {
+            let _ = sender.send(Err(anyhow::anyhow!("{}", reason)));
+        }
     }
 
-    /// Receive a message (for handling server-initiated requests/notifications)
-    pub async fn receive(&mut self) -> Result<JsonRpcMessage> {
-        self.transport.receive().await
+    /// Route a message received from the server
+    fn dispatch(&self, message: JsonRpcMessage, replies: &mpsc::UnboundedSender<JsonRpcResponse>) {
+        match message {
+            JsonRpcMessage::Response(response) => {
+                let result = match response.result_or_error {
+                    ResultOrError::Success { result } => Ok(result),
+                    ResultOrError::Error { error } => Err(error.into()),
+                };
+                self.complete(&response.id, result);
+            }
+            JsonRpcMessage::Request(JsonRpcRequest::Notification { method, params, .. }) => {
+                // No subscribers is not an error
+                let _ = self.notifications.send(Notification { method, params });
+            }
+            JsonRpcMessage::Request(JsonRpcRequest::Request { id, method, params, .. }) => {
+                // Handlers may take a while (sampling runs the LLM), so they
+                // run on their own task and must not block the I/O loop
+                let handlers = self.handlers.read().unwrap().clone();
+                let replies = replies.clone();
+                tokio::spawn(async move {
+                    let response = match handle_server_request(&handlers, &method, params).await {
+                        Ok(result) => JsonRpcResponse::success(id, result),
+                        Err(error) => JsonRpcResponse::error(id, error),
+                    };
+                    let _ = replies.send(response);
+                });
+            }
+        }
     }
 
-    /// Check if the transport is still alive
-    pub fn is_alive(&mut self) -> bool {
-        self.transport.is_alive()
+    /// Hand a result to the request waiting on `id`
+    fn complete(&self, id: &Value, result: Result<Value>) {
+        let waiter = id
+            .as_u64()
+            .and_then(|id| self.pending.lock().unwrap().remove(&id));
+
+        match waiter {
+            // The caller may have given up waiting
+            Some(tx) => {
+                let _ = tx.send(result);
+            }
+            // Late responses to cancelled requests end up here too
+            None => debug!(id = %id, "Received response for unknown request"),
+        }
     }
 }
 
+/// Run the handler registered for a server-initiated request
+async fn handle_server_request(
+    handlers: &Handlers,
+    method: &str,
+    params: Option<Value>,
+) -> std::result::Result<Value, JsonRpcError> {
+    fn parse<T: serde::de::DeserializeOwned>(
+        params: Option<Value>,
+    ) -> std::result::Result<T, JsonRpcError> {
+        serde_json::from_value(params.unwrap_or(Value::Null))
+            .map_err(|e| JsonRpcError::invalid_params(Some(Value::String(e.to_string()))))
+    }
+
+    let result: Result<Value> = match (method, handlers) {
+        ("ping", _) => Ok(serde_json::json!({})),
+        ("sampling/createMessage", Handlers { sampling: Some(handler), .. }) => {
+            let params: CreateMessageParams = parse(params)?;
+            handler
+                .create_message(params)
+                .await
+                .and_then(|r| Ok(serde_json::to_value(r)?))
+        }
+        ("roots/list", Handlers { roots: Some(handler), .. }) => handler
+            .list_roots()
+            .await
+            .and_then(|roots| Ok(serde_json::to_value(ListRootsResult { roots })?)),
+        ("elicitation/create", Handlers { elicitation: Some(handler), .. }) => {
+            let params: ElicitParams = parse(params)?;
+            handler
+                .elicit(params)
+                .await
+                .and_then(|r| Ok(serde_json::to_value(r)?))
+        }
+        _ => {
+            debug!(method = %method, "Rejecting unsupported server request");
+            return Err(JsonRpcError::method_not_found(Some(Value::String(method.to_string()))));
+        }
+    };
+
+    result.map_err(|e| JsonRpcError::internal_error(Some(Value::String(format!("{:#}", e)))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use serde_json::json;
+
+    /// In-memory transport; the test plays the server on the other end
+    struct ChannelTransport {
+        to_server: mpsc::UnboundedSender<JsonRpcMessage>,
+        from_server: mpsc::UnboundedReceiver<JsonRpcMessage>,
+    }
+
+    #[async_trait]
+    impl Transport for ChannelTransport {
+        async fn send(&mut self, message: &JsonRpcMessage) -> Result<()> {
+            self.to_server.send(message.clone())?;
+            Ok(())
+        }
+
+        async fn receive(&mut self) -> Result<JsonRpcMessage> {
+            self.from_server.recv().await.context("closed")
+        }
+    }
+
+    fn client_pair() -> (
+        McpClient,
+        mpsc::UnboundedReceiver<JsonRpcMessage>,
+        mpsc::UnboundedSender<JsonRpcMessage>,
+    ) {
+        let (to_server, server_rx) = mpsc::unbounded_channel();
+        let (server_tx, from_server) = mpsc::unbounded_channel();
+        let client = McpClient::new(ChannelTransport {
+            to_server,
+            from_server,
+        });
+        (client, server_rx, server_tx)
+    }
+
+    fn request_id(message: JsonRpcMessage) -> Value {
+        match message {
+            JsonRpcMessage::Request(JsonRpcRequest::Request { id, .. }) => id,
+            other => panic!("Expected request, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_routed_by_id() {
+        let (client, mut server_rx, server_tx) = client_pair();
+        let client = Arc::new(client);
+
+        let first = tokio::spawn({
+            let client = Arc::clone(&client);
+            async move { client.request("first", None).await }
+        });
+        let first_id = request_id(server_rx.recv().await.unwrap());
+
+        let second = tokio::spawn({
+            let client = Arc::clone(&client);
+            async move { client.request("second", None).await }
+        });
+        let second_id = request_id(server_rx.recv().await.unwrap());
+
+        // Answer out of order
+        server_tx
+            .send(JsonRpcMessage::Response(JsonRpcResponse::success(second_id, json!("two"))))
+            .unwrap();
+        server_tx
+            .send(JsonRpcMessage::Response(JsonRpcResponse::success(first_id, json!("one"))))
+            .unwrap();
+
+        assert_eq!(first.await.unwrap().unwrap(), json!("one"));
+        assert_eq!(second.await.unwrap().unwrap(), json!("two"));
+    }
+
+    #[tokio::test]
+    async fn test_error_response() {
+        let (client, mut server_rx, server_tx) = client_pair();
+
+        let pending = tokio::spawn(async move { client.request("missing", None).await });
+        let id = request_id(server_rx.recv().await.unwrap());
+        server_tx
+            .send(JsonRpcMessage::Response(JsonRpcResponse::error(
+                id,
+                JsonRpcError::method_not_found(None),
+            )))
+            .unwrap();
+
+        let err = pending.await.unwrap().unwrap_err();
+        assert_eq!(err.downcast_ref::<JsonRpcError>().unwrap().code, -32601);
+    }
+
+    #[tokio::test]
+    async fn test_notifications_broadcast() {
+        let (client, _server_rx, server_tx) = client_pair();
+        let mut notifications = client.subscribe();
+
+        server_tx
+            .send(JsonRpcMessage::Request(JsonRpcRequest::notification(
+                "notifications/tools/list_changed",
+                None,
+            )))
+            .unwrap();
+
+        let notification = notifications.recv().await.unwrap();
+        assert_eq!(notification.method, "notifications/tools/list_changed");
+    }
+
+    #[tokio::test]
+    async fn test_list_resources_follows_cursor() {
+        let (client, mut server_rx, server_tx) = client_pair();
+
+        let pending = tokio::spawn(async move { client.list_resources().await });
+
+        let pages = [
+            json!({"resources": [{"uri": "docs://a", "name": "a"}], "nextCursor": "page2"}),
+            json!({"resources": [{"uri": "docs://b", "name": "b", "mimeType": "text/plain"}]}),
+        ];
+        for (i, page) in pages.into_iter().enumerate() {
+            let request = match server_rx.recv().await.unwrap() {
+                JsonRpcMessage::Request(request) => request,
+                other => panic!("Expected request, got {:?}", other),
+            };
+            let JsonRpcRequest::Request { id, method, params, .. } = request else {
+                panic!("Expected request with id");
+            };
+            assert_eq!(method, "resources/list");
+            if i == 1 {
+                assert_eq!(params.unwrap()["cursor"], "page2");
+            }
+            server_tx
+                .send(JsonRpcMessage::Response(JsonRpcResponse::success(id, page)))
+                .unwrap();
+        }
+
+        let resources = pending.await.unwrap().unwrap();
+        let uris: Vec<_> = resources.iter().map(|r| r.uri.as_str()).collect();
+        assert_eq!(uris, ["docs://a", "docs://b"]);
+        assert_eq!(resources[1].mime_type.as_deref(), Some("text/plain"));
+    }
+
+    #[tokio::test]
+    async fn test_get_prompt() {
+        let (client, mut server_rx, server_tx) = client_pair();
+
+        let pending = tokio::spawn(async move {
+            let arguments = HashMap::from([("code".to_string(), "fn main() {}".to_string())]);
+            client.get_prompt("code_review", arguments).await
+        });
+        let id = request_id(server_rx.recv().await.unwrap());
+        server_tx
+            .send(JsonRpcMessage::Response(JsonRpcResponse::success(
+                id,
+                json!({
+                    "description": "Review code",
+                    "messages": [
+                        {"role": "user", "content": {"type": "text", "text": "Review fn main() {}"}}
+                    ]
+                }),
+            )))
+            .unwrap();
+
+        let prompt = pending.await.unwrap().unwrap();
+        assert_eq!(prompt.messages.len(), 1);
+        assert!(matches!(
+            &prompt.messages[0].content,
+            crate::mcp::types::ContentBlock::Text { text } if text == "Review fn main() {}"
+        ));
+    }
+
+    struct EchoSampler;
+
+    #[async_trait]
+    impl SamplingHandler for EchoSampler {
+        async fn create_message(
+            &self,
+            params: CreateMessageParams,
+        ) -> Result<crate::mcp::types::CreateMessageResult> {
+            Ok(crate::mcp::types::CreateMessageResult {
+                role: "assistant".to_string(),
+                content: params.messages[0].content.clone(),
+                model: "echo".to_string(),
+                stop_reason: Some("endTurn".to_string()),
+            })
+        }
+    }
+
+    fn response_of(message: JsonRpcMessage) -> JsonRpcResponse {
+        match message {
+            JsonRpcMessage::Response(response) => response,
+            other => panic!("Expected response, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sampling_request_answered_by_handler() {
+        let (client, mut server_rx, server_tx) = client_pair();
+        let _client = client.with_sampling_handler(Arc::new(EchoSampler));
+
+        server_tx
+            .send(JsonRpcMessage::Request(JsonRpcRequest::new(
+                json!("s-1"),
+                "sampling/createMessage",
+                Some(json!({
+                    "messages": [{"role": "user", "content": {"type": "text", "text": "hi"}}],
+                    "maxTokens": 16
+                })),
+            )))
+            .unwrap();
+
+        let response = response_of(server_rx.recv().await.unwrap());
+        assert_eq!(response.id, json!("s-1"));
+        match response.result_or_error {
+            ResultOrError::Success { result } => {
+                assert_eq!(result["content"]["text"], "hi");
+                assert_eq!(result["model"], "echo");
+            }
+            ResultOrError::Error { error } => panic!("Unexpected error: {}", error),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unhandled_server_request_rejected() {
+        let (_client, mut server_rx, server_tx) = client_pair();
+
+        server_tx
+            .send(JsonRpcMessage::Request(JsonRpcRequest::new(json!(7), "roots/list", None)))
+            .unwrap();
+
+        let response = response_of(server_rx.recv().await.unwrap());
+        match response.result_or_error {
+            ResultOrError::Error { error } => assert_eq!(error.code, -32601),
+            ResultOrError::Success { .. } => panic!("Expected method not found"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pending_requests_fail_when_transport_closes() {
+        let (client, mut server_rx, server_tx) = client_pair();
+
+        let pending = tokio::spawn(async move { client.request("slow", None).await });
+        server_rx.recv().await.unwrap();
+        drop(server_tx);
+
+        assert!(pending.await.unwrap().is_err());
+    }
+
+    fn cancelled_request_id(message: JsonRpcMessage) -> Value {
+        match message {
+            JsonRpcMessage::Request(JsonRpcRequest::Notification { method, params, .. }) => {
+                assert_eq!(method, "notifications/cancelled");
+                params.unwrap()["requestId"].clone()
+            }
+            other => panic!("Expected cancellation, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout_cancels_request() {
+        let (client, mut server_rx, _server_tx) = client_pair();
+
+        let result = client
+            .request_with_timeout("slow", None, Some(std::time::Duration::from_millis(20)))
+            .await;
+
+        let error = result.unwrap_err();
+        let timeout = error.downcast_ref::<RequestTimeout>().expect("Expected timeout error");
+        assert_eq!(timeout.method, "slow");
+
+        let id = request_id(server_rx.recv().await.unwrap());
+        assert_eq!(cancelled_request_id(server_rx.recv().await.unwrap()), id);
+        assert!(client.shared.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_request_is_cancelled() {
+        let (client, mut server_rx, _server_tx) = client_pair();
+        let client = Arc::new(client);
+
+        let pending = tokio::spawn({
+            let client = Arc::clone(&client);
+            async move { client.request("slow", None).await }
+        });
+        let id = request_id(server_rx.recv().await.unwrap());
+        pending.abort();
+
+        assert_eq!(cancelled_request_id(server_rx.recv().await.unwrap()), id);
+    }
+
+    fn method(message: JsonRpcMessage) -> String {
+        match message {
+            JsonRpcMessage::Request(request) => request.method().to_string(),
+            other => panic!("Expected request, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_supervised_client_restarts_and_replays_handshake() {
+        // Every connection hands its server end to the test
+        let (servers_tx, mut servers) = mpsc::unbounded_channel();
+        let connect = move || {
+            let (to_server, server_rx) = mpsc::unbounded_channel();
+            let (server_tx, from_server) = mpsc::unbounded_channel();
+            let _ = servers_tx.send((server_rx, server_tx));
+            async move { Ok(ChannelTransport { to_server, from_server }) }
+        };
+        let policy = RestartPolicy {
+            initial_backoff: std::time::Duration::from_millis(1),
+            ..Default::default()
+        };
+        let client = Arc::new(McpClient::new_supervised(connect, policy).await.unwrap());
+        let init_result = json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {},
+            "serverInfo": {"name": "test", "version": "1.0"}
+        });
+
+        // The first server completes the handshake, then crashes
+        let (mut server_rx, server_tx) = servers.recv().await.unwrap();
+        let initialize = tokio::spawn({
+            let client = Arc::clone(&client);
+            async move { client.initialize().await }
+        });
+        let id = request_id(server_rx.recv().await.unwrap());
+        server_tx
+            .send(JsonRpcMessage::Response(JsonRpcResponse::success(id, init_result.clone())))
+            .unwrap();
+        initialize.await.unwrap().unwrap();
+        assert_eq!(method(server_rx.recv().await.unwrap()), "notifications/initialized");
+        drop(server_tx);
+
+        // The replacement receives the replayed handshake
+        let (mut server_rx, server_tx) = servers.recv().await.unwrap();
+        let replayed = server_rx.recv().await.unwrap();
+        assert_eq!(method(replayed.clone()), "initialize");
+        server_tx
+            .send(JsonRpcMessage::Response(JsonRpcResponse::success(
+                request_id(replayed),
+                init_result,
+            )))
+            .unwrap();
+        assert_eq!(method(server_rx.recv().await.unwrap()), "notifications/initialized");
+
+        let ping = tokio::spawn({
+            let client = Arc::clone(&client);
+            async move { client.request("ping", None).await }
+        });
+        let id = request_id(server_rx.recv().await.unwrap());
+        server_tx
+            .send(JsonRpcMessage::Response(JsonRpcResponse::success(id, json!({}))))
+            .unwrap();
+        assert!(ping.await.unwrap().is_ok());
+        assert!(client.is_alive());
+    }
+}
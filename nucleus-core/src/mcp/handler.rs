@@ -0,0 +1,357 @@
+//! Handlers for server-initiated MCP requests
+//!
+//! MCP servers can ask the client for things during a session:
+//! - `sampling/createMessage`: generate a completion with the client's LLM
+//! - `roots/list`: list the directories the client exposes to the server
+//! - `elicitation/create`: ask the user for structured input
+//!
+//! Each is handled by a pluggable trait object registered on the
+//! [`McpClient`](crate::mcp::McpClient). The client only advertises the
+//! capabilities it has a handler for.
+
+use crate::config::Config;
+use crate::mcp::types::{
+    ContentBlock, CreateMessageParams, CreateMessageResult, ElicitParams, ElicitResult, Root,
+};
+use crate::provider::{ChatRequest, Message, Provider, ProviderError, SamplingParams};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use tokio_util::sync::CancellationToken;
+
+/// Answers `sampling/createMessage` requests
+#[async_trait]
+pub trait SamplingHandler: Send + Sync {
+    /// Generate a message for the server's prompt
+    async fn create_message(&self, params: CreateMessageParams) -> Result<CreateMessageResult>;
+}
+
+/// Answers `roots/list` requests
+#[async_trait]
+pub trait RootsHandler: Send + Sync {
+    /// List the roots exposed to the server
+    async fn list_roots(&self) -> Result<Vec<Root>>;
+}
+
+/// Answers `elicitation/create` requests
+#[async_trait]
+pub trait ElicitationHandler: Send + Sync {
+    /// Ask the user for the information the server requested
+    async fn elicit(&self, params: ElicitParams) -> Result<ElicitResult>;
+}
+
+/// Samples with a nucleus [`Provider`], so servers that rely on client-side
+/// sampling run against local models.
+///
+/// The server's model preferences are ignored; the configured model is always used.
+pub struct ProviderSamplingHandler {
+    provider: Arc<OnceLock<Arc<dyn Provider>>>,
+    model: String,
+    temperature: f64,
+    sampling: SamplingParams,
+    enable_thinking: Option<bool>,
+}
+
+impl ProviderSamplingHandler {
+    /// Create a handler using the model and sampling settings from `config.llm`
+    pub fn new(provider: Arc<dyn Provider>, config: &Config) -> Self {
+        let (handler, slot) = Self::deferred(config);
+        let _ = slot.set(provider);
+        handler
+    }
+
+    /// Create a handler whose provider is set later through the returned slot
+    ///
+    /// MCP servers are connected before the provider is created, since the
+    /// provider needs their tools. Until the slot is set, sampling requests fail.
+    pub(crate) fn deferred(config: &Config) -> (Self, Arc<OnceLock<Arc<dyn Provider>>>) {
+        let slot = Arc::new(OnceLock::new());
+        let handler = Self {
+            provider: Arc::clone(&slot),
+            model: config.llm.model.clone(),
+            temperature: config.llm.temperature,
+            sampling: config.llm.sampling.clone(),
+            enable_thinking: config.llm.enable_thinking,
+        };
+        (handler, slot)
+    }
+}
+
+#[async_trait]
+impl SamplingHandler for ProviderSamplingHandler {
+    async fn create_message(&self, params: CreateMessageParams) -> Result<CreateMessageResult> {
+        let provider = self.provider.get().context("Sampling provider is not ready yet")?;
+
+        let mut messages = Vec::with_capacity(params.messages.len() + 1);
+        if let Some(system_prompt) = params.system_prompt {
+            messages.push(Message::system(None, system_prompt));
+        }
+
+        for sampling_message in params.messages {
+            let mut message = match sampling_message.role.as_str() {
+                "assistant" => Message::assistant(None, ""),
+                _ => Message::user(None, ""),
+            };
+            match sampling_message.content {
+                ContentBlock::Text { text } => message.content = text,
+                ContentBlock::Image { data, .. } => message.images = Some(vec![data]),
+                other => anyhow::bail!("Unsupported sampling content: {:?}", other),
+            }
+            messages.push(message);
+        }
+
+        // Stop sequences are matched here rather than by the provider, since
+        // providers don't report whether one of them ended the response
+        let max_tokens = params.max_tokens as usize;
+        let stop_sequences = params.stop_sequences.unwrap_or_else(|| self.sampling.stop.clone());
+        let stop = CancellationToken::new();
+        let request = ChatRequest::new(&self.model, messages)
+            .with_temperature(params.temperature.unwrap_or(self.temperature))
+            .with_sampling(SamplingParams {
+                max_tokens: Some(max_tokens),
+                stop: Vec::new(),
+                ..self.sampling.clone()
+            })
+            .with_thinking(self.enable_thinking)
+            .with_cancellation(stop.clone());
+
+        let mut content = String::new();
+        let mut final_content = String::new();
+        let mut usage = None;
+        let result = provider
+            .chat(request, Box::new(|response| {
+                content.push_str(&response.content);
+                if response.done {
+                    final_content = response.message.content;
+                    usage = response.usage;
+                }
+                if find_stop_sequence(&content, &stop_sequences).is_some() {
+                    stop.cancel();
+                }
+            }))
+            .await;
+        match result {
+            Err(ProviderError::Cancelled) if stop.is_cancelled() => {}
+            result => result.context("Sampling request to provider failed")?,
+        }
+
+        // Some providers only deliver the full text in the final chunk
+        if content.is_empty() {
+            content = final_content;
+        }
+
+        let stop_reason = if let Some(end) = find_stop_sequence(&content, &stop_sequences) {
+            content.truncate(end);
+            "stopSequence"
+        } else if usage.is_some_and(|usage| usage.completion_tokens >= max_tokens) {
+            "maxTokens"
+        } else {
+            "endTurn"
+        };
+
+        Ok(CreateMessageResult {
+            role: "assistant".to_string(),
+            content: ContentBlock::Text { text: content },
+            model: self.model.clone(),
+            stop_reason: Some(stop_reason.to_string()),
+        })
+    }
+}
+
+/// Byte offset of the first stop sequence in `text`, if any
+fn find_stop_sequence(text: &str, stop_sequences: &[String]) -> Option<usize> {
+    stop_sequences
+        .iter()
+        .filter(|sequence| !sequence.is_empty())
+        .filter_map(|sequence| text.find(sequence.as_str()))
+        .min()
+}
+
+/// Exposes a fixed list of workspace directories as roots
+pub struct StaticRootsHandler {
+    roots: Vec<Root>,
+}
+
+impl StaticRootsHandler {
+    /// Create a handler exposing the given directories
+    ///
+    /// Relative paths are resolved against the current working directory.
+    pub fn from_paths<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Result<Self> {
+        let roots = paths
+            .into_iter()
+            .map(|path| root_for_path(path.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { roots })
+    }
+
+    /// Create a handler exposing the workspace directories in `config.mcp.roots`
+    ///
+    /// Falls back to the current working directory if none are configured.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        if config.mcp.roots.is_empty() {
+            let cwd = std::env::current_dir().context("Failed to get current directory")?;
+            Self::from_paths([cwd])
+        } else {
+            Self::from_paths(&config.mcp.roots)
+        }
+    }
+}
+
+#[async_trait]
+impl RootsHandler for StaticRootsHandler {
+    async fn list_roots(&self) -> Result<Vec<Root>> {
+        Ok(self.roots.clone())
+    }
+}
+
+/// Build a `file://` root for a local path
+fn root_for_path(path: &Path) -> Result<Root> {
+    let absolute: PathBuf = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .context("Failed to get current directory")?
+            .join(path)
+    };
+
+    let name = absolute
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string());
+    let path_str = absolute.to_string_lossy().replace('\\', "/");
+    let uri = if path_str.starts_with('/') {
+        format!("file://{}", path_str)
+    } else {
+        // Windows drive paths, e.g. C:/work
+        format!("file:///{}", path_str)
+    };
+
+    Ok(Root { uri, name })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::types::SamplingMessage;
+    use crate::models::EmbeddingModel;
+    use crate::provider::{ChatResponse, Result as ProviderResult, Usage};
+
+    /// Streams a fixed answer one word per chunk, one token per word
+    struct WordProvider(&'static str);
+
+    #[async_trait]
+    impl Provider for WordProvider {
+        async fn chat<'a>(
+            &'a self,
+            request: ChatRequest,
+            mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+        ) -> ProviderResult<()> {
+            assert!(request.sampling.stop.is_empty());
+            let max_tokens = request.sampling.max_tokens.unwrap_or(usize::MAX);
+            let words: Vec<&str> = self.0.split_inclusive(' ').take(max_tokens).collect();
+            let cancel = request.cancel.unwrap_or_default();
+
+            for (i, word) in words.iter().enumerate() {
+                if cancel.is_cancelled() {
+                    return Err(ProviderError::Cancelled);
+                }
+                let done = i + 1 == words.len();
+                callback(ChatResponse {
+                    model: request.model.clone(),
+                    content: word.to_string(),
+                    reasoning: String::new(),
+                    done,
+                    message: Message::assistant(None, ""),
+                    usage: done.then_some(Usage { prompt_tokens: 1, completion_tokens: words.len() }),
+                });
+            }
+            Ok(())
+        }
+
+        async fn embed(&self, _text: &str, _model: &EmbeddingModel) -> ProviderResult<Vec<f32>> {
+            Ok(Vec::new())
+        }
+    }
+
+    async fn sample(answer: &'static str, max_tokens: u32, stop_sequences: &[&str]) -> CreateMessageResult {
+        let handler = ProviderSamplingHandler::new(Arc::new(WordProvider(answer)), &Config::default());
+        handler
+            .create_message(CreateMessageParams {
+                messages: vec![SamplingMessage {
+                    role: "user".to_string(),
+                    content: ContentBlock::Text { text: "Hi".to_string() },
+                }],
+                model_preferences: None,
+                system_prompt: None,
+                include_context: None,
+                temperature: None,
+                max_tokens,
+                stop_sequences: Some(stop_sequences.iter().map(|s| s.to_string()).collect()),
+                metadata: None,
+            })
+            .await
+            .unwrap()
+    }
+
+    fn text(result: &CreateMessageResult) -> &str {
+        match &result.content {
+            ContentBlock::Text { text } => text,
+            other => panic!("unexpected content: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sampling_stop_reasons() {
+        let result = sample("one two three", 10, &[]).await;
+        assert_eq!(text(&result), "one two three");
+        assert_eq!(result.stop_reason.as_deref(), Some("endTurn"));
+
+        let result = sample("one two three four", 2, &[]).await;
+        assert_eq!(text(&result), "one two ");
+        assert_eq!(result.stop_reason.as_deref(), Some("maxTokens"));
+
+        let result = sample("one two. three four", 10, &["."]).await;
+        assert_eq!(text(&result), "one two");
+        assert_eq!(result.stop_reason.as_deref(), Some("stopSequence"));
+    }
+
+    #[tokio::test]
+    async fn test_deferred_sampling_handler() {
+        let (handler, slot) = ProviderSamplingHandler::deferred(&Config::default());
+        let params = CreateMessageParams {
+            messages: Vec::new(),
+            model_preferences: None,
+            system_prompt: None,
+            include_context: None,
+            temperature: None,
+            max_tokens: 10,
+            stop_sequences: None,
+            metadata: None,
+        };
+        assert!(handler.create_message(params.clone()).await.is_err());
+
+        let _ = slot.set(Arc::new(WordProvider("ready")));
+        let result = handler.create_message(params).await.unwrap();
+        assert_eq!(text(&result), "ready");
+    }
+
+    #[tokio::test]
+    async fn test_static_roots() {
+        let handler = StaticRootsHandler::from_paths(["/home/user/project"]).unwrap();
+        let roots = handler.list_roots().await.unwrap();
+
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots[0].uri, "file:///home/user/project");
+        assert_eq!(roots[0].name.as_deref(), Some("project"));
+    }
+
+    #[tokio::test]
+    async fn test_relative_roots_are_absolute() {
+        let handler = StaticRootsHandler::from_paths(["src"]).unwrap();
+        let roots = handler.list_roots().await.unwrap();
+
+        assert!(roots[0].uri.starts_with("file://"));
+        assert!(roots[0].uri.ends_with("/src"));
+    }
+}
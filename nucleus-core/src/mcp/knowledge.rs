@@ -0,0 +1,100 @@
+//! MCP resources as RAG knowledge
+//!
+//! Feeds resources served by an MCP server (documentation, schemas, notes)
+//! into a [`RagEngine`], so they can be retrieved as context like indexed files.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use nucleus_core::mcp::{add_mcp_resources, McpClient};
+//! use nucleus_core::rag::RagEngine;
+//!
+//! # async fn example(rag: RagEngine) -> anyhow::Result<()> {
+//! let client = McpClient::new_http("http://localhost:3000/mcp");
+//! client.initialize().await?;
+//!
+//! let count = add_mcp_resources(&rag, &client).await?;
+//! println!("Added {} MCP resources to the knowledge base", count);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::mcp::client::McpClient;
+use crate::mcp::types::ResourceContents;
+use crate::rag::RagEngine;
+use anyhow::{Context, Result};
+use tracing::{debug, warn};
+
+/// Read every resource listed by the server and add its text to the knowledge base
+///
+/// Binary resources are skipped. Resources that fail to read are logged and
+/// skipped rather than aborting the whole import.
+///
+/// # Returns
+///
+/// The number of text contents added.
+pub async fn add_mcp_resources(rag: &RagEngine, client: &McpClient) -> Result<usize> {
+    let resources = client.list_resources().await?;
+
+    let mut added = 0;
+    for resource in resources {
+        match add_mcp_resource(rag, client, &resource.uri).await {
+            Ok(count) => added += count,
+            Err(e) => warn!(uri = %resource.uri, error = %e, "Failed to add MCP resource"),
+        }
+    }
+
+    Ok(added)
+}
+
+/// Read a single resource and add its text to the knowledge base
+///
+/// Each text content is stored with its URI as the source.
+///
+/// # Returns
+///
+/// The number of text contents added.
+pub async fn add_mcp_resource(rag: &RagEngine, client: &McpClient, uri: &str) -> Result<usize> {
+    let contents = client.read_resource(uri).await?;
+
+    let texts = text_contents(&contents);
+    for (source, text) in &texts {
+        rag.add_knowledge(text, source)
+            .await
+            .with_context(|| format!("Failed to add {} to knowledge base", source))?;
+        debug!(uri = %source, "Added MCP resource to knowledge base");
+    }
+
+    Ok(texts.len())
+}
+
+/// Non-empty text contents paired with their URIs
+fn text_contents(contents: &[ResourceContents]) -> Vec<(&str, &str)> {
+    contents
+        .iter()
+        .filter_map(|content| match content {
+            ResourceContents::Text { uri, text, .. } if !text.trim().is_empty() => {
+                Some((uri.as_str(), text.as_str()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_text_contents_skips_blobs_and_empty_text() {
+        let contents: Vec<ResourceContents> = serde_json::from_value(json!([
+            {"uri": "docs://guide", "mimeType": "text/markdown", "text": "# Guide"},
+            {"uri": "docs://logo", "mimeType": "image/png", "blob": "aGVsbG8="},
+            {"uri": "docs://empty", "text": "  "}
+        ]))
+        .unwrap();
+
+        assert_eq!(text_contents(&contents), vec![("docs://guide", "# Guide")]);
+    }
+}
@@ -1,13 +1,32 @@
-//! MCP (Model Context Protocol) client implementation
+//! MCP (Model Context Protocol) client and server implementation
 //!
 //! This module provides a client for communicating with MCP servers
-//! using various transports (stdio, HTTP, WebSocket).
+//! using various transports (stdio, HTTP, WebSocket), adapters that
+//! register MCP server tools as plugins and feed MCP resources into the RAG
+//! knowledge base, and a server exposing nucleus plugins to other MCP clients.
 
 pub mod client;
+pub mod handler;
+pub mod knowledge;
+pub mod plugin;
+pub mod server;
+pub mod servers;
+pub mod supervisor;
 pub mod transport;
 pub mod types;
 
-pub use client::McpClient;
-pub use transport::{http::HttpTransport, stdio::StdioTransport};
+pub use client::{McpClient, RequestTimeout, DEFAULT_REQUEST_TIMEOUT};
+pub use handler::{
+    ElicitationHandler, ProviderSamplingHandler, RootsHandler, SamplingHandler,
+    StaticRootsHandler,
+};
+pub use knowledge::{add_mcp_resource, add_mcp_resources};
+pub use plugin::{register_mcp_tools, McpToolPlugin};
+pub use server::McpServer;
+pub use servers::{connect_server, register_configured_servers};
+pub use supervisor::RestartPolicy;
+pub use transport::{
+    http::HttpTransport, stdio::StdioTransport, websocket::WebSocketTransport, Transport,
+};
 pub use types::{JsonRpcError, JsonRpcMessage, JsonRpcRequest, JsonRpcResponse};
 
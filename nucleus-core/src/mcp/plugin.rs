@@ -0,0 +1,289 @@
+//! MCP tools as nucleus plugins
+//!
+//! Bridges tools exposed by an MCP server into a [`PluginRegistry`], so the LLM
+//! can call them through `ChatManager` exactly like built-in plugins such as
+//! `ReadFilePlugin`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use nucleus_core::mcp::{register_mcp_tools, McpClient};
+//! use nucleus_plugin::{Permission, PluginRegistry};
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let client = McpClient::new_stdio("npx", &["-y", "@modelcontextprotocol/server-everything"])?;
+//! let client = Arc::new(client);
+//!
+//! let mut registry = PluginRegistry::new(Permission::ALL);
+//! let count = register_mcp_tools(&mut registry, client).await?;
+//! println!("Registered {} MCP tools", count);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::mcp::client::McpClient;
+use crate::mcp::types::{CallToolResult, ContentBlock, JsonRpcError, McpTool, ResourceContents};
+use anyhow::Result;
+use async_trait::async_trait;
+use nucleus_plugin::{Permission, Plugin, PluginError, PluginOutput, PluginRegistry};
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// A tool on a remote MCP server, exposed as a [`Plugin`].
+///
+/// Executing the plugin issues a `tools/call` request through the shared client.
+pub struct McpToolPlugin {
+    client: Arc<McpClient>,
+    tool: McpTool,
+    name: String,
+    description: String,
+    permission: Permission,
+}
+
+impl McpToolPlugin {
+    /// Create a plugin for `tool`, served by `client`.
+    ///
+    /// The required permission is derived from the tool's annotations: tools
+    /// marked `readOnlyHint` require [`Permission::READ_ONLY`], everything else
+    /// requires [`Permission::ALL`] since the server may run arbitrary code.
+    pub fn new(client: Arc<McpClient>, tool: McpTool) -> Self {
+        let description = tool
+            .description
+            .clone()
+            .or_else(|| tool.title.clone())
+            .unwrap_or_default();
+
+        let read_only = tool
+            .annotations
+            .as_ref()
+            .and_then(|a| a.read_only_hint)
+            .unwrap_or(false);
+        let permission = if read_only {
+            Permission::READ_ONLY
+        } else {
+            Permission::ALL
+        };
+
+        Self {
+            client,
+            name: tool.name.clone(),
+            tool,
+            description,
+            permission,
+        }
+    }
+
+    /// Override the permission derived from the tool's annotations.
+    pub fn with_permission(mut self, permission: Permission) -> Self {
+        self.permission = permission;
+        self
+    }
+
+    /// Register the tool as `<namespace>__<tool>` instead of its bare name.
+    ///
+    /// The server is still called with the tool's original name.
+    pub fn with_namespace(mut self, namespace: &str) -> Self {
+        self.name = format!("{}__{}", namespace, self.tool.name);
+        self
+    }
+
+    /// The MCP tool definition this plugin wraps.
+    pub fn tool(&self) -> &McpTool {
+        &self.tool
+    }
+}
+
+#[async_trait]
+impl Plugin for McpToolPlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameter_schema(&self) -> Value {
+        self.tool.input_schema.clone()
+    }
+
+    fn required_permission(&self) -> Permission {
+        self.permission
+    }
+
+    async fn execute(&self, input: Value) -> nucleus_plugin::Result<PluginOutput> {
+        let result = self
+            .client
+            .call_tool(&self.tool.name, input)
+            .await
+            .map_err(|e| match e.downcast_ref::<JsonRpcError>() {
+                Some(rpc) if rpc.code == JsonRpcError::INVALID_PARAMS => {
+                    PluginError::InvalidInput(rpc.message.clone())
+                }
+                _ => PluginError::ExecutionFailed(format!("{:#}", e)),
+            })?;
+
+        into_plugin_output(result)
+    }
+}
+
+/// Initialize `client` if needed, list its tools and register each one as a plugin.
+///
+/// Tools denied by the registry's granted permissions are skipped.
+///
+/// # Returns
+///
+/// The number of tools that were registered.
+pub async fn register_mcp_tools(
+    registry: &mut PluginRegistry,
+    client: Arc<McpClient>,
+) -> Result<usize> {
+    if client.server_info().is_none() {
+        client.initialize().await?;
+    }
+    let tools = client.list_tools().await?;
+
+    let mut registered = 0;
+    for tool in tools {
+        let plugin = McpToolPlugin::new(Arc::clone(&client), tool);
+        let name = plugin.name().to_string();
+
+        if registry.register(Arc::new(plugin)) {
+            debug!(tool_name = %name, "Registered MCP tool");
+            registered += 1;
+        } else {
+            warn!(tool_name = %name, "MCP tool denied by registry permissions");
+        }
+    }
+
+    Ok(registered)
+}
+
+/// Convert an MCP `tools/call` result into plugin output.
+///
+/// Text blocks (and embedded text resources) become the output content. Other
+/// blocks are summarised inline and kept in full in the output metadata.
+/// Results flagged with `isError` become [`PluginError::ExecutionFailed`].
+fn into_plugin_output(result: CallToolResult) -> nucleus_plugin::Result<PluginOutput> {
+    let mut has_non_text = false;
+    let parts: Vec<String> = result
+        .content
+        .iter()
+        .map(|block| match block {
+            ContentBlock::Text { text } => text.clone(),
+            ContentBlock::Image { mime_type, .. } => {
+                has_non_text = true;
+                format!("[image: {}]", mime_type)
+            }
+            ContentBlock::Audio { mime_type, .. } => {
+                has_non_text = true;
+                format!("[audio: {}]", mime_type)
+            }
+            ContentBlock::Resource { resource } => match resource {
+                ResourceContents::Text { text, .. } => text.clone(),
+                ResourceContents::Blob { uri, mime_type, .. } => {
+                    has_non_text = true;
+                    match mime_type {
+                        Some(mime) => format!("[resource: {} ({})]", uri, mime),
+                        None => format!("[resource: {}]", uri),
+                    }
+                }
+            },
+            ContentBlock::ResourceLink { uri, name, .. } => {
+                has_non_text = true;
+                format!("[resource link: {} <{}>]", name, uri)
+            }
+        })
+        .collect();
+
+    let mut content = parts.join("\n");
+    if content.is_empty() {
+        if let Some(structured) = &result.structured_content {
+            content = structured.to_string();
+        }
+    }
+
+    if result.is_error {
+        return Err(PluginError::ExecutionFailed(content));
+    }
+
+    let mut output = PluginOutput::new(content);
+    if has_non_text || result.structured_content.is_some() {
+        output = output.with_metadata(serde_json::json!({
+            "content": result.content,
+            "structuredContent": result.structured_content,
+        }));
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(value: Value) -> CallToolResult {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_text_content() {
+        let result = parse(json!({
+            "content": [
+                {"type": "text", "text": "first"},
+                {"type": "text", "text": "second"}
+            ]
+        }));
+
+        let output = into_plugin_output(result).unwrap();
+        assert_eq!(output.content, "first\nsecond");
+        assert!(output.metadata.is_none());
+    }
+
+    #[test]
+    fn test_image_and_resource_content() {
+        let result = parse(json!({
+            "content": [
+                {"type": "image", "data": "aGVsbG8=", "mimeType": "image/png"},
+                {"type": "resource", "resource": {"uri": "file:///a.txt", "text": "embedded"}},
+                {"type": "resource_link", "uri": "file:///b.txt", "name": "b.txt"}
+            ]
+        }));
+
+        let output = into_plugin_output(result).unwrap();
+        assert_eq!(
+            output.content,
+            "[image: image/png]\nembedded\n[resource link: b.txt <file:///b.txt>]"
+        );
+        let metadata = output.metadata.unwrap();
+        assert_eq!(metadata["content"][0]["data"], "aGVsbG8=");
+    }
+
+    #[test]
+    fn test_is_error_maps_to_execution_failed() {
+        let result = parse(json!({
+            "content": [{"type": "text", "text": "city not found"}],
+            "isError": true
+        }));
+
+        match into_plugin_output(result) {
+            Err(PluginError::ExecutionFailed(msg)) => assert_eq!(msg, "city not found"),
+            other => panic!("Expected ExecutionFailed, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_structured_content_fallback() {
+        let result = parse(json!({
+            "content": [],
+            "structuredContent": {"temperature": 21}
+        }));
+
+        let output = into_plugin_output(result).unwrap();
+        assert_eq!(output.content, r#"{"temperature":21}"#);
+        assert!(output.metadata.is_some());
+    }
+}
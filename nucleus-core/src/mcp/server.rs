@@ -0,0 +1,533 @@
+//! MCP server exposing nucleus plugins
+//!
+//! The reverse direction of [`McpClient`](crate::mcp::McpClient): serves the
+//! plugins in a [`PluginRegistry`] as MCP tools, so editors and other agents
+//! can use `ReadFilePlugin`, `SearchPlugin` and custom plugins over MCP.
+//!
+//! Supported methods are `initialize`, `ping`, `tools/list` and `tools/call`.
+//! Two transports are available:
+//! - stdio: newline-delimited JSON-RPC on stdin/stdout ([`McpServer::serve_stdio`])
+//! - streamable HTTP: JSON-RPC via POST to `/mcp` ([`McpServer::serve_http`])
+//!
+//! # Permissions
+//!
+//! Only plugins the registry accepted are served. The server can narrow this
+//! further with [`McpServer::with_permission`]; plugins requiring more than
+//! that are neither listed nor callable.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use nucleus_core::mcp::McpServer;
+//! use nucleus_plugin::{Permission, PluginRegistry};
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let registry = PluginRegistry::new(Permission::READ_ONLY);
+//! // registry.register(Arc::new(ReadFilePlugin::new()));
+//!
+//! let server = Arc::new(McpServer::new(Arc::new(registry)));
+//! server.serve_stdio().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::mcp::types::{
+    CallToolParams, CallToolResult, ContentBlock, Implementation, InitializeResult, JsonRpcError,
+    JsonRpcMessage, JsonRpcRequest, JsonRpcResponse, ListToolsResult, McpTool,
+    ServerCapabilities, ToolAnnotations, PROTOCOL_VERSION,
+};
+use anyhow::{Context, Result};
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use nucleus_plugin::{Permission, Plugin, PluginRegistry};
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+/// Protocol versions the server can speak, newest first
+const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &[PROTOCOL_VERSION, "2025-03-26", "2024-11-05"];
+
+/// MCP server serving the plugins of a [`PluginRegistry`]
+pub struct McpServer {
+    registry: Arc<PluginRegistry>,
+    permission: Permission,
+    server_info: Implementation,
+    instructions: Option<String>,
+}
+
+impl McpServer {
+    /// Create a server exposing every plugin in `registry`
+    pub fn new(registry: Arc<PluginRegistry>) -> Self {
+        Self {
+            registry,
+            permission: Permission::ALL,
+            server_info: Implementation::default(),
+            instructions: None,
+        }
+    }
+
+    /// Only expose plugins whose required permission is allowed by `permission`
+    pub fn with_permission(mut self, permission: Permission) -> Self {
+        self.permission = permission;
+        self
+    }
+
+    /// Override the name and version reported to clients
+    pub fn with_server_info(mut self, name: impl Into<String>, version: impl Into<String>) -> Self {
+        self.server_info = Implementation {
+            name: name.into(),
+            version: version.into(),
+        };
+        self
+    }
+
+    /// Set usage instructions sent to clients during initialization
+    pub fn with_instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.instructions = Some(instructions.into());
+        self
+    }
+
+    /// Handle a single JSON-RPC message
+    ///
+    /// Returns the response to send back, or `None` for notifications and
+    /// responses, which need no reply.
+    pub async fn handle_message(&self, message: JsonRpcMessage) -> Option<JsonRpcResponse> {
+        match message {
+            JsonRpcMessage::Request(JsonRpcRequest::Request { id, method, params, .. }) => {
+                let response = match self.handle_request(&method, params).await {
+                    Ok(result) => JsonRpcResponse::success(id, result),
+                    Err(error) => JsonRpcResponse::error(id, error),
+                };
+                Some(response)
+            }
+            JsonRpcMessage::Request(JsonRpcRequest::Notification { method, .. }) => {
+                debug!(method = %method, "Received MCP notification");
+                None
+            }
+            JsonRpcMessage::Response(response) => {
+                debug!(id = %response.id, "Ignoring response from MCP client");
+                None
+            }
+        }
+    }
+
+    /// Serve over stdin/stdout until stdin is closed
+    pub async fn serve_stdio(self: Arc<Self>) -> Result<()> {
+        let stdin = tokio::io::BufReader::new(tokio::io::stdin());
+        self.serve(stdin, tokio::io::stdout()).await
+    }
+
+    /// Serve newline-delimited JSON-RPC over any reader/writer pair
+    ///
+    /// Requests are handled concurrently, so responses may be written in a
+    /// different order than the requests arrived.
+    pub async fn serve<R, W>(self: Arc<Self>, reader: R, mut writer: W) -> Result<()>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (response_tx, mut response_rx) = mpsc::unbounded_channel::<JsonRpcMessage>();
+
+        let write_task = tokio::spawn(async move {
+            while let Some(message) = response_rx.recv().await {
+                let json = serde_json::to_string(&message)?;
+                writer.write_all(json.as_bytes()).await?;
+                writer.write_all(b"\n").await?;
+                writer.flush().await?;
+            }
+            Ok::<_, anyhow::Error>(())
+        });
+
+        let mut lines = reader.lines();
+        while let Some(line) = lines.next_line().await.context("Failed to read from input")? {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let message = match serde_json::from_str::<JsonRpcMessage>(&line) {
+                Ok(message) => message,
+                Err(e) => {
+                    warn!(error = %e, "Failed to parse MCP message");
+                    let response = JsonRpcResponse::error(
+                        Value::Null,
+                        JsonRpcError::parse_error(Some(Value::String(e.to_string()))),
+                    );
+                    let _ = response_tx.send(JsonRpcMessage::Response(response));
+                    continue;
+                }
+            };
+
+            let server = Arc::clone(&self);
+            let response_tx = response_tx.clone();
+            tokio::spawn(async move {
+                if let Some(response) = server.handle_message(message).await {
+                    let _ = response_tx.send(JsonRpcMessage::Response(response));
+                }
+            });
+        }
+
+        // Let in-flight requests finish writing their responses
+        drop(response_tx);
+        write_task.await.context("MCP writer task panicked")?
+    }
+
+    /// Serve streamable HTTP on `addr` until the process is stopped
+    ///
+    /// Clients POST JSON-RPC messages to `/mcp`. Bind to a loopback address
+    /// unless the plugins are safe to expose to the network.
+    pub async fn serve_http(self: Arc<Self>, addr: impl ToSocketAddrs) -> Result<()> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .context("Failed to bind MCP HTTP server")?;
+        info!(addr = %listener.local_addr()?, "MCP server listening");
+
+        axum::serve(listener, self.router())
+            .await
+            .context("MCP HTTP server failed")
+    }
+
+    /// Build the HTTP router, for embedding the server in an existing axum app
+    pub fn router(self: Arc<Self>) -> Router {
+        Router::new()
+            .route("/mcp", post(handle_http_post).get(handle_http_get))
+            .with_state(self)
+    }
+
+    async fn handle_request(
+        &self,
+        method: &str,
+        params: Option<Value>,
+    ) -> std::result::Result<Value, JsonRpcError> {
+        match method {
+            "initialize" => to_value(self.initialize(params)),
+            "ping" => Ok(serde_json::json!({})),
+            "tools/list" => to_value(ListToolsResult {
+                tools: self.tools(),
+                next_cursor: None,
+            }),
+            "tools/call" => {
+                let params: CallToolParams =
+                    serde_json::from_value(params.unwrap_or(Value::Null)).map_err(|e| {
+                        JsonRpcError::invalid_params(Some(Value::String(e.to_string())))
+                    })?;
+                to_value(self.call_tool(params).await?)
+            }
+            _ => Err(JsonRpcError::method_not_found(Some(Value::String(
+                method.to_string(),
+            )))),
+        }
+    }
+
+    fn initialize(&self, params: Option<Value>) -> InitializeResult {
+        // Speak the client's version if we support it, otherwise offer ours
+        let requested = params
+            .as_ref()
+            .and_then(|p| p.get("protocolVersion"))
+            .and_then(Value::as_str);
+        let protocol_version = match requested {
+            Some(version) if SUPPORTED_PROTOCOL_VERSIONS.contains(&version) => version,
+            _ => PROTOCOL_VERSION,
+        };
+
+        InitializeResult {
+            protocol_version: protocol_version.to_string(),
+            capabilities: ServerCapabilities {
+                tools: Some(serde_json::json!({ "listChanged": false })),
+                ..Default::default()
+            },
+            server_info: self.server_info.clone(),
+            instructions: self.instructions.clone(),
+        }
+    }
+
+    /// Plugins visible to clients, sorted by name
+    fn visible_plugins(&self) -> Vec<&Arc<dyn Plugin>> {
+        let mut plugins: Vec<_> = self
+            .registry
+            .all()
+            .into_iter()
+            .filter(|plugin| self.permission.allows(&plugin.required_permission()))
+            .collect();
+        plugins.sort_by(|a, b| a.name().cmp(b.name()));
+        plugins
+    }
+
+    fn tools(&self) -> Vec<McpTool> {
+        self.visible_plugins()
+            .into_iter()
+            .map(|plugin| {
+                let permission = plugin.required_permission();
+                McpTool {
+                    name: plugin.name().to_string(),
+                    title: None,
+                    description: Some(plugin.description().to_string()),
+                    input_schema: plugin.parameter_schema(),
+                    output_schema: None,
+                    annotations: Some(ToolAnnotations {
+                        read_only_hint: Some(!permission.write && !permission.execute),
+                        ..Default::default()
+                    }),
+                }
+            })
+            .collect()
+    }
+
+    async fn call_tool(
+        &self,
+        params: CallToolParams,
+    ) -> std::result::Result<CallToolResult, JsonRpcError> {
+        let plugin = self
+            .visible_plugins()
+            .into_iter()
+            .find(|plugin| plugin.name() == params.name)
+            .cloned()
+            .ok_or_else(|| {
+                JsonRpcError::invalid_params(Some(Value::String(format!(
+                    "Unknown tool: {}",
+                    params.name
+                ))))
+            })?;
+
+        let input = params
+            .arguments
+            .unwrap_or_else(|| Value::Object(Default::default()));
+        info!(tool_name = %params.name, "Executing tool for MCP client");
+
+        // Tool failures are reported in the result so the calling model can see them
+        let result = match plugin.execute(input).await {
+            Ok(output) => CallToolResult {
+                content: vec![ContentBlock::Text {
+                    text: output.content,
+                }],
+                structured_content: None,
+                is_error: false,
+            },
+            Err(e) => CallToolResult {
+                content: vec![ContentBlock::Text {
+                    text: e.to_string(),
+                }],
+                structured_content: None,
+                is_error: true,
+            },
+        };
+
+        Ok(result)
+    }
+}
+
+fn to_value<T: serde::Serialize>(value: T) -> std::result::Result<Value, JsonRpcError> {
+    serde_json::to_value(value)
+        .map_err(|e| JsonRpcError::internal_error(Some(Value::String(e.to_string()))))
+}
+
+/// Reject browser requests from non-local origins (DNS rebinding protection)
+fn origin_allowed(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN).and_then(|o| o.to_str().ok()) else {
+        return true;
+    };
+
+    let authority = origin
+        .split_once("://")
+        .map_or(origin, |(_, rest)| rest)
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    let host = match authority.find(']') {
+        Some(end) => &authority[..=end],
+        None => authority.split(':').next().unwrap_or_default(),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+}
+
+async fn handle_http_post(
+    State(server): State<Arc<McpServer>>,
+    headers: HeaderMap,
+    Json(message): Json<JsonRpcMessage>,
+) -> Response {
+    if !origin_allowed(&headers) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    match server.handle_message(message).await {
+        Some(response) => Json(response).into_response(),
+        // Notifications and responses are acknowledged without a body
+        None => StatusCode::ACCEPTED.into_response(),
+    }
+}
+
+/// The server never initiates messages, so there is no SSE stream to open
+async fn handle_http_get() -> StatusCode {
+    StatusCode::METHOD_NOT_ALLOWED
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::types::ResultOrError;
+    use async_trait::async_trait;
+    use nucleus_plugin::{PluginError, PluginOutput};
+    use serde_json::json;
+
+    struct EchoPlugin;
+
+    #[async_trait]
+    impl Plugin for EchoPlugin {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn description(&self) -> &str {
+            "Echo the input text"
+        }
+
+        fn parameter_schema(&self) -> Value {
+            json!({"type": "object", "properties": {"text": {"type": "string"}}})
+        }
+
+        fn required_permission(&self) -> Permission {
+            Permission::READ_ONLY
+        }
+
+        async fn execute(&self, input: Value) -> nucleus_plugin::Result<PluginOutput> {
+            match input.get("text").and_then(Value::as_str) {
+                Some(text) => Ok(PluginOutput::new(text)),
+                None => Err(PluginError::InvalidInput("missing text".to_string())),
+            }
+        }
+    }
+
+    struct DeletePlugin;
+
+    #[async_trait]
+    impl Plugin for DeletePlugin {
+        fn name(&self) -> &str {
+            "delete"
+        }
+
+        fn description(&self) -> &str {
+            "Delete a file"
+        }
+
+        fn parameter_schema(&self) -> Value {
+            json!({"type": "object"})
+        }
+
+        fn required_permission(&self) -> Permission {
+            Permission::READ_WRITE
+        }
+
+        async fn execute(&self, _input: Value) -> nucleus_plugin::Result<PluginOutput> {
+            Ok(PluginOutput::new("deleted"))
+        }
+    }
+
+    fn server(permission: Permission) -> McpServer {
+        let mut registry = PluginRegistry::new(Permission::ALL);
+        registry.register(Arc::new(EchoPlugin));
+        registry.register(Arc::new(DeletePlugin));
+        McpServer::new(Arc::new(registry)).with_permission(permission)
+    }
+
+    async fn call(server: &McpServer, method: &str, params: Value) -> ResultOrError {
+        let request = JsonRpcRequest::new(json!(1), method, Some(params));
+        server
+            .handle_message(JsonRpcMessage::Request(request))
+            .await
+            .unwrap()
+            .result_or_error
+    }
+
+    fn success(result: ResultOrError) -> Value {
+        match result {
+            ResultOrError::Success { result } => result,
+            ResultOrError::Error { error } => panic!("Unexpected error: {}", error),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_initialize() {
+        let server = server(Permission::ALL);
+        let result = success(call(&server, "initialize", json!({"protocolVersion": "2024-11-05"})).await);
+
+        assert_eq!(result["protocolVersion"], "2024-11-05");
+        assert_eq!(result["serverInfo"]["name"], "nucleus");
+        assert!(result["capabilities"]["tools"].is_object());
+    }
+
+    #[tokio::test]
+    async fn test_tools_list_respects_permission() {
+        let server = server(Permission::READ_ONLY);
+        let result = success(call(&server, "tools/list", json!({})).await);
+
+        let tools = result["tools"].as_array().unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0]["name"], "echo");
+        assert_eq!(tools[0]["annotations"]["readOnlyHint"], true);
+    }
+
+    #[tokio::test]
+    async fn test_tools_call() {
+        let server = server(Permission::ALL);
+        let result = success(
+            call(&server, "tools/call", json!({"name": "echo", "arguments": {"text": "hi"}})).await,
+        );
+
+        assert_eq!(result["content"][0]["text"], "hi");
+        assert_eq!(result["isError"], false);
+    }
+
+    #[tokio::test]
+    async fn test_tools_call_plugin_error_is_tool_error() {
+        let server = server(Permission::ALL);
+        let result = success(call(&server, "tools/call", json!({"name": "echo", "arguments": {}})).await);
+
+        assert_eq!(result["isError"], true);
+        assert!(result["content"][0]["text"].as_str().unwrap().contains("missing text"));
+    }
+
+    #[tokio::test]
+    async fn test_tools_call_hidden_tool_rejected() {
+        let server = server(Permission::READ_ONLY);
+        match call(&server, "tools/call", json!({"name": "delete"})).await {
+            ResultOrError::Error { error } => assert_eq!(error.code, JsonRpcError::INVALID_PARAMS),
+            ResultOrError::Success { .. } => panic!("Hidden tool should not be callable"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_over_stream() {
+        let server = Arc::new(server(Permission::ALL));
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#, "\n",
+            r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#, "\n",
+        );
+        let (client, server_end) = tokio::io::duplex(4096);
+
+        server.serve(input.as_bytes(), server_end).await.unwrap();
+
+        let mut output = String::new();
+        let mut client = tokio::io::BufReader::new(client);
+        client.read_line(&mut output).await.unwrap();
+        let response: JsonRpcResponse = serde_json::from_str(&output).unwrap();
+        assert_eq!(response.id, json!(1));
+    }
+
+    #[test]
+    fn test_origin_validation() {
+        let mut headers = HeaderMap::new();
+        assert!(origin_allowed(&headers));
+
+        headers.insert(header::ORIGIN, "http://localhost:3000".parse().unwrap());
+        assert!(origin_allowed(&headers));
+
+        headers.insert(header::ORIGIN, "https://evil.example.com".parse().unwrap());
+        assert!(!origin_allowed(&headers));
+    }
+}
@@ -0,0 +1,208 @@
+//! MCP servers declared in configuration
+//!
+//! Connects to the servers listed under `mcp.servers` and registers their
+//! tools as plugins, namespaced per server (`<server>__<tool>`). Stdio
+//! servers are supervised and restarted if they crash.
+//!
+//! ```yaml
+//! mcp:
+//!   servers:
+//!     - name: github
+//!       transport: stdio
+//!       command: npx
+//!       args: ["-y", "@modelcontextprotocol/server-github"]
+//!       env:
+//!         GITHUB_TOKEN: ghp_...
+//!       tools: [search_repositories, get_file_contents]
+//!     - name: docs
+//!       transport: http
+//!       url: http://localhost:3000/mcp
+//!       headers:
+//!         Authorization: Bearer ...
+//!       timeout_secs: 10
+//! ```
+
+use crate::config::{Config, McpServerConfig, McpTransportConfig};
+use crate::mcp::client::McpClient;
+use crate::mcp::handler::{SamplingHandler, StaticRootsHandler};
+use crate::mcp::plugin::McpToolPlugin;
+use crate::mcp::supervisor::RestartPolicy;
+use crate::mcp::transport::http::HttpTransport;
+use crate::mcp::transport::stdio::StdioTransport;
+use crate::mcp::transport::websocket::WebSocketTransport;
+use anyhow::{Context, Result};
+use nucleus_plugin::{Plugin, PluginRegistry};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Connect to every server in `config.mcp.servers` and register its tools
+///
+/// A server that fails to start or connect is logged and skipped, so one
+/// unavailable server doesn't prevent the others from being used.
+///
+/// With a `sampling` handler, the servers can request completions from the
+/// client's LLM.
+///
+/// # Returns
+///
+/// The clients of the servers that connected successfully.
+pub async fn register_configured_servers(
+    registry: &mut PluginRegistry,
+    config: &Config,
+    sampling: Option<Arc<dyn SamplingHandler>>,
+) -> Vec<Arc<McpClient>> {
+    let mut clients = Vec::new();
+
+    for server in &config.mcp.servers {
+        match connect_and_register(registry, config, server, sampling.clone()).await {
+            Ok((client, count)) => {
+                info!(server = %server.name, tools = count, "Connected to MCP server");
+                clients.push(client);
+            }
+            Err(e) => {
+                let error = format!("{:#}", e);
+                warn!(server = %server.name, error = %error, "Failed to set up MCP server");
+            }
+        }
+    }
+
+    clients
+}
+
+async fn connect_and_register(
+    registry: &mut PluginRegistry,
+    config: &Config,
+    server: &McpServerConfig,
+    sampling: Option<Arc<dyn SamplingHandler>>,
+) -> Result<(Arc<McpClient>, usize)> {
+    let timeout = Duration::from_secs(server.timeout_secs);
+
+    let client = tokio::time::timeout(timeout, async {
+        let mut client = connect_server(server)
+            .await?
+            .with_request_timeout(Some(timeout))
+            .with_roots_handler(Arc::new(StaticRootsHandler::from_config(config)?));
+        if let Some(sampling) = sampling {
+            client = client.with_sampling_handler(sampling);
+        }
+        client.initialize().await?;
+        Ok::<_, anyhow::Error>(Arc::new(client))
+    })
+    .await
+    .context("Timed out connecting to MCP server")??;
+
+    let count = tokio::time::timeout(timeout, register_server_tools(registry, Arc::clone(&client), server))
+        .await
+        .context("Timed out listing MCP server tools")??;
+
+    Ok((client, count))
+}
+
+/// Create a client for a configured server, spawning or connecting as needed
+///
+/// The returned client has not been initialized yet.
+pub async fn connect_server(server: &McpServerConfig) -> Result<McpClient> {
+    let client = match &server.transport {
+        McpTransportConfig::Stdio { command, args, env } => {
+            let (command, args, env) = (command.clone(), args.clone(), env.clone());
+            let spawn = move || {
+                let args: Vec<&str> = args.iter().map(String::as_str).collect();
+                let transport = StdioTransport::spawn_with_env(&command, &args, &env)
+                    .with_context(|| format!("Failed to spawn MCP server '{}'", command));
+                async move { transport }
+            };
+
+            if server.max_restarts == 0 {
+                McpClient::new(spawn().await?)
+            } else {
+                let policy = RestartPolicy {
+                    max_restarts: server.max_restarts,
+                    ..Default::default()
+                };
+                McpClient::new_supervised(spawn, policy).await?
+            }
+        }
+        McpTransportConfig::Http { url, headers } => {
+            // Only connecting is limited here; the client times out each request
+            let http_client = reqwest::Client::builder()
+                .default_headers(header_map(headers)?)
+                .connect_timeout(Duration::from_secs(server.timeout_secs))
+                .build()
+                .context("Failed to build HTTP client")?;
+            McpClient::new(HttpTransport::with_client(http_client, url))
+        }
+        McpTransportConfig::WebSocket { url, headers } => {
+            let transport = WebSocketTransport::connect_with_headers(url, headers).await?;
+            McpClient::new(transport)
+        }
+    };
+
+    Ok(client)
+}
+
+/// Register the tools of an initialized server, filtered by its allow-list
+///
+/// # Returns
+///
+/// The number of tools that were registered.
+pub async fn register_server_tools(
+    registry: &mut PluginRegistry,
+    client: Arc<McpClient>,
+    server: &McpServerConfig,
+) -> Result<usize> {
+    let tools = client.list_tools().await?;
+
+    for allowed in &server.tools {
+        if !tools.iter().any(|tool| &tool.name == allowed) {
+            warn!(server = %server.name, tool_name = %allowed, "Allowed MCP tool not offered by server");
+        }
+    }
+
+    let mut registered = 0;
+    for tool in tools {
+        if !server.tools.is_empty() && !server.tools.contains(&tool.name) {
+            continue;
+        }
+
+        let plugin = McpToolPlugin::new(Arc::clone(&client), tool).with_namespace(&server.name);
+        let name = plugin.name().to_string();
+
+        if registry.register(Arc::new(plugin)) {
+            registered += 1;
+        } else {
+            warn!(tool_name = %name, "MCP tool denied by registry permissions");
+        }
+    }
+
+    Ok(registered)
+}
+
+fn header_map(headers: &HashMap<String, String>) -> Result<HeaderMap> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!("Invalid header name: {}", name))?;
+        let value = HeaderValue::from_str(value)
+            .with_context(|| format!("Invalid value for header {}", name))?;
+        map.insert(name, value);
+    }
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_map() {
+        let headers = HashMap::from([("Authorization".to_string(), "Bearer token".to_string())]);
+        let map = header_map(&headers).unwrap();
+        assert_eq!(map["authorization"], "Bearer token");
+
+        let invalid = HashMap::from([("Bad Header".to_string(), "x".to_string())]);
+        assert!(header_map(&invalid).is_err());
+    }
+}
@@ -0,0 +1,160 @@
+//! Restarting crashed MCP servers
+//!
+//! A supervised [`McpClient`](crate::mcp::McpClient) recreates its transport
+//! when the server goes away (e.g. a stdio server process crashes), with
+//! exponential backoff between attempts. After reconnecting, the `initialize`
+//! handshake the client performed is replayed so the new server is ready for
+//! requests. Requests that were in flight when the server died fail.
+
+use crate::mcp::transport::Transport;
+use crate::mcp::types::{JsonRpcMessage, JsonRpcRequest, ResultOrError};
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+/// Time to wait for the server to answer the replayed `initialize` request
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A server that ran this long before dying is considered healthy again,
+/// so its restart count and backoff start over
+const STABLE_PERIOD: Duration = Duration::from_secs(60);
+
+/// Creates a fresh transport to the server
+pub(crate) type Connect = Box<dyn Fn() -> BoxFuture<'static, Result<Box<dyn Transport>>> + Send + Sync>;
+
+/// How often and how quickly a crashed server is restarted
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    /// Consecutive restarts before giving up
+    pub max_restarts: u32,
+    /// Delay before the first restart, doubled for each consecutive one
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between restarts
+    pub max_backoff: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Restart state owned by the client's I/O task
+pub(crate) struct Supervisor {
+    connect: Connect,
+    policy: RestartPolicy,
+    /// The client's `initialize` request, replayed after each restart
+    initialize: Option<JsonRpcRequest>,
+    /// Whether the client sent `notifications/initialized`
+    initialized: bool,
+    restarts: u32,
+    started: Instant,
+}
+
+impl Supervisor {
+    pub(crate) fn new(connect: Connect, policy: RestartPolicy) -> Self {
+        Self {
+            connect,
+            policy,
+            initialize: None,
+            initialized: false,
+            restarts: 0,
+            started: Instant::now(),
+        }
+    }
+
+    /// Create the first transport
+    pub(crate) async fn connect(&self) -> Result<Box<dyn Transport>> {
+        (self.connect)().await
+    }
+
+    /// Record the handshake messages sent by the client
+    pub(crate) fn observe(&mut self, message: &JsonRpcMessage) {
+        let JsonRpcMessage::Request(request) = message else {
+            return;
+        };
+        match request.method() {
+            "initialize" => self.initialize = Some(request.clone()),
+            "notifications/initialized" => self.initialized = true,
+            _ => {}
+        }
+    }
+
+    /// Reconnect with backoff and replay the handshake
+    ///
+    /// Returns `None` once the restart limit is reached.
+    pub(crate) async fn restart(&mut self) -> Option<Box<dyn Transport>> {
+        if self.started.elapsed() >= STABLE_PERIOD {
+            self.restarts = 0;
+        }
+
+        loop {
+            if self.restarts >= self.policy.max_restarts {
+                error!(restarts = self.restarts, "MCP server keeps failing, giving up");
+                return None;
+            }
+
+            let backoff = self
+                .policy
+                .initial_backoff
+                .saturating_mul(2u32.saturating_pow(self.restarts))
+                .min(self.policy.max_backoff);
+            self.restarts += 1;
+            tokio::time::sleep(backoff).await;
+
+            info!(attempt = self.restarts, "Restarting MCP server");
+            let result = async {
+                let mut transport = self.connect().await?;
+                self.handshake(transport.as_mut()).await?;
+                Ok::<_, anyhow::Error>(transport)
+            }
+            .await;
+
+            match result {
+                Ok(transport) => {
+                    self.started = Instant::now();
+                    return Some(transport);
+                }
+                Err(e) => warn!(error = %format!("{:#}", e), "Failed to restart MCP server"),
+            }
+        }
+    }
+
+    /// Replay the client's `initialize` handshake on a new transport
+    async fn handshake(&self, transport: &mut dyn Transport) -> Result<()> {
+        let Some(request @ JsonRpcRequest::Request { id, .. }) = &self.initialize else {
+            // The client never initialized, so neither does the new server
+            return Ok(());
+        };
+
+        transport.send(&JsonRpcMessage::Request(request.clone())).await?;
+
+        tokio::time::timeout(HANDSHAKE_TIMEOUT, async {
+            loop {
+                // Servers may log or ping before answering; nothing else is pending yet
+                if let JsonRpcMessage::Response(response) = transport.receive().await? {
+                    if response.id == *id {
+                        if let ResultOrError::Error { error } = response.result_or_error {
+                            return Err(anyhow::Error::new(error));
+                        }
+                        return Ok(());
+                    }
+                }
+            }
+        })
+        .await
+        .context("Timed out waiting for initialize response")??;
+
+        if self.initialized {
+            let notification = JsonRpcRequest::notification("notifications/initialized", None);
+            transport.send(&JsonRpcMessage::Request(notification)).await?;
+        }
+
+        Ok(())
+    }
+}
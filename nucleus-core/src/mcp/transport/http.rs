@@ -1,66 +1,226 @@
 //! HTTP transport for MCP
 //!
-//! Handles communication over HTTP using JSON-RPC messages sent via POST requests.
-//! Used for remote MCP servers accessible over HTTP.
+//! Implements the streamable HTTP transport: every outgoing message is sent
+//! via POST, and the server answers with either a single JSON body or an SSE
+//! (`text/event-stream`) stream carrying any number of messages.
+//!
+//! Requests are sent from background tasks, so several can be in flight at
+//! once even when the server answers each with a plain JSON body only after
+//! the work is done. SSE streams are parsed incrementally, so progress
+//! notifications and server requests arrive while a long-running tool call is
+//! still in progress. If a stream drops before the response arrives, it is
+//! resumed with a GET carrying `Last-Event-ID`. After initialization a
+//! standalone GET stream is kept open for messages the server sends on its own.
+//!
+//! Responses aren't timed out here: [`McpClient`](crate::mcp::McpClient)
+//! times out each request and sends `notifications/cancelled`, which aborts
+//! the request's task.
 
-use crate::mcp::types::{JsonRpcMessage, JsonRpcRequest, JsonRpcResponse};
+use super::Transport;
+use crate::mcp::types::{JsonRpcError, JsonRpcMessage, JsonRpcRequest, JsonRpcResponse};
 use anyhow::{Context, Result};
-use reqwest::Client;
+use async_trait::async_trait;
+use futures::StreamExt;
+use reqwest::{Client, Response, StatusCode};
 use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::{AbortHandle, JoinSet};
+use tracing::{debug, warn};
+
+/// Reconnection delay used until the server sends a `retry` field
+const DEFAULT_RETRY: Duration = Duration::from_secs(1);
+
+/// Upper bound for the standalone stream's reconnection backoff
+const MAX_RETRY: Duration = Duration::from_secs(30);
+
+/// Attempts to resume a dropped response stream before giving up on the request
+const MAX_RESUME_ATTEMPTS: u32 = 3;
+
+/// How long the server may take to acknowledge a notification or response
+const ACKNOWLEDGE_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// HTTP transport for MCP communication
+///
+/// Requests are sent on background tasks, so a slow response doesn't hold up
+/// other messages. Messages from response bodies and SSE streams are queued
+/// and handed out by [`receive`](Transport::receive).
 pub struct HttpTransport {
     client: Client,
     server_url: String,
-    next_id: u64,
-    session_id: Option<String>,
+    /// Assigned by the server when answering `initialize`
+    session_id: Arc<Mutex<Option<String>>>,
+    standalone_stream: bool,
+    incoming_tx: mpsc::UnboundedSender<JsonRpcMessage>,
+    incoming_rx: mpsc::UnboundedReceiver<JsonRpcMessage>,
+    /// Requests awaiting their response and background SSE readers; aborted
+    /// when the transport is closed or dropped
+    tasks: JoinSet<()>,
+    /// Tasks of requests awaiting their response, by request id
+    requests: HashMap<String, AbortHandle>,
 }
 
 impl HttpTransport {
     /// Create a new HTTP transport with a server URL
     pub fn new(server_url: impl Into<String>) -> Self {
-        Self {
-            client: Client::new(),
-            server_url: server_url.into(),
-            next_id: 1,
-            session_id: None,
-        }
+        Self::with_client(Client::new(), server_url)
     }
 
     /// Create a new HTTP transport with a custom HTTP client
     pub fn with_client(client: Client, server_url: impl Into<String>) -> Self {
+        let (incoming_tx, incoming_rx) = mpsc::unbounded_channel();
         Self {
             client,
             server_url: server_url.into(),
-            next_id: 1,
-            session_id: None,
+            session_id: Arc::new(Mutex::new(None)),
+            standalone_stream: true,
+            incoming_tx,
+            incoming_rx,
+            tasks: JoinSet::new(),
+            requests: HashMap::new(),
+        }
+    }
+
+    /// Enable or disable the standalone GET stream opened after initialization
+    ///
+    /// Enabled by default. Servers that don't offer one answer 405, which is
+    /// handled quietly.
+    pub fn with_standalone_stream(mut self, enabled: bool) -> Self {
+        self.standalone_stream = enabled;
+        self
+    }
+
+    /// Get the server URL
+    pub fn server_url(&self) -> &str {
+        &self.server_url
+    }
+
+    /// Get the session ID assigned by the server, if any
+    pub fn session_id(&self) -> Option<String> {
+        self.session_id.lock().unwrap().clone()
+    }
+
+    fn event_stream(&self) -> EventStream {
+        EventStream {
+            client: self.client.clone(),
+            server_url: self.server_url.clone(),
+            session_id: Arc::clone(&self.session_id),
+            incoming: self.incoming_tx.clone(),
+            parser: SseParser::default(),
         }
     }
+}
 
-    /// Send a JSON-RPC message and receive a response
+#[async_trait]
+impl Transport for HttpTransport {
+    /// Send a JSON-RPC message as a POST request
+    ///
+    /// A request is posted on a background task, which queues its response
+    /// for [`receive`](Transport::receive) whether it arrives as a JSON body
+    /// or an SSE stream. Notifications and responses are acknowledged right
+    /// away (202 Accepted), so they are sent inline and keep their order.
     ///
-    /// Note: HTTP transport only supports request/response pattern.
-    /// Notifications are sent but no response is expected.
-    pub async fn send(&mut self, message: &JsonRpcMessage) -> Result<Option<JsonRpcResponse>> {
+    /// Sending `notifications/cancelled` also aborts the cancelled request,
+    /// which the server may never answer.
+    async fn send(&mut self, message: &JsonRpcMessage) -> Result<()> {
+        // Reap finished requests and stream readers
+        while self.tasks.try_join_next().is_some() {}
+        self.requests.retain(|_, task| !task.is_finished());
+
         // Serialize the message
         let json_body = serde_json::to_value(message)
             .context("Failed to serialize JSON-RPC message")?;
 
-        // Send POST request
+        let stream = self.event_stream();
+        match message {
+            JsonRpcMessage::Request(JsonRpcRequest::Request { id, .. }) => {
+                let task = self.tasks.spawn(stream.run_request(json_body, id.clone()));
+                self.requests.insert(id.to_string(), task);
+            }
+            JsonRpcMessage::Request(JsonRpcRequest::Notification { method, params, .. }) => {
+                if method == "notifications/cancelled" {
+                    let request_id = params.as_ref().and_then(|params| params.get("requestId"));
+                    if let Some(task) = request_id.and_then(|id| self.requests.remove(&id.to_string())) {
+                        task.abort();
+                    }
+                }
+                stream.acknowledged_post(&json_body).await?;
+                // The session is ready for server-initiated messages
+                if method == "notifications/initialized" && self.standalone_stream {
+                    self.tasks.spawn(stream.run_standalone());
+                }
+            }
+            JsonRpcMessage::Response(_) => {
+                stream.acknowledged_post(&json_body).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Receive the next message from a response body or SSE stream
+    async fn receive(&mut self) -> Result<JsonRpcMessage> {
+        self.incoming_rx
+            .recv()
+            .await
+            .context("HTTP transport closed")
+    }
+
+    /// Stop reading streams and terminate the session, if the server assigned one
+    async fn close(&mut self) -> Result<()> {
+        self.tasks.abort_all();
+
+        let session_id = self.session_id.lock().unwrap().take();
+        if let Some(session_id) = session_id {
+            self.client
+                .delete(&self.server_url)
+                .header("Mcp-Session-Id", session_id)
+                .send()
+                .await
+                .context("Failed to terminate HTTP session")?;
+        }
+        Ok(())
+    }
+}
+
+/// State of one exchange with the server: a request and its response, or
+/// an SSE stream shared across its reconnections
+struct EventStream {
+    client: Client,
+    server_url: String,
+    session_id: Arc<Mutex<Option<String>>>,
+    incoming: mpsc::UnboundedSender<JsonRpcMessage>,
+    parser: SseParser,
+}
+
+impl EventStream {
+    /// POST a notification or response, which the server acknowledges right away
+    async fn acknowledged_post(&self, body: &Value) -> Result<()> {
+        tokio::time::timeout(ACKNOWLEDGE_TIMEOUT, self.post(body))
+            .await
+            .context("Timed out waiting for the server to acknowledge a message")??;
+        Ok(())
+    }
+
+    /// POST a message, recording the session ID the server assigns
+    async fn post(&self, body: &Value) -> Result<Response> {
         // MCP servers typically require Accept header for both application/json and text/event-stream
         let mut request_builder = self
             .client
             .post(&self.server_url)
             .header("Accept", "application/json, text/event-stream")
             .header("Content-Type", "application/json");
-        
+
         // Add session ID if we have one
-        if let Some(session_id) = &self.session_id {
+        let session_id = self.session_id.lock().unwrap().clone();
+        if let Some(session_id) = session_id {
             request_builder = request_builder.header("Mcp-Session-Id", session_id);
         }
-        
+
         let response = request_builder
-            .json(&json_body)
+            .json(body)
             .send()
             .await
             .context("Failed to send HTTP request")?;
@@ -76,28 +236,26 @@ impl HttpTransport {
             );
         }
 
-        // Handle notifications (no response expected)
-        match message {
-            JsonRpcMessage::Request(JsonRpcRequest::Notification { .. }) => {
-                // Notifications don't expect a response
-                return Ok(None);
-            }
-            JsonRpcMessage::Request(JsonRpcRequest::Request { .. }) => {
-                // Requests expect a response
-            }
-            JsonRpcMessage::Response(_) => {
-                // Responses shouldn't be sent via HTTP (they're received)
-                anyhow::bail!("Cannot send a response via HTTP transport");
-            }
-        }
-
         // Extract session ID from response headers if present
         if let Some(session_id_header) = response.headers().get("mcp-session-id") {
             if let Ok(session_id) = session_id_header.to_str() {
-                self.session_id = Some(session_id.to_string());
+                *self.session_id.lock().unwrap() = Some(session_id.to_string());
             }
         }
-        
+
+        Ok(response)
+    }
+
+    /// Send request `id` and forward its response
+    ///
+    /// If the request fails, an error response is queued so the pending
+    /// request fails instead of waiting forever.
+    async fn run_request(self, body: Value, id: Value) {
+        let response = match self.post(&body).await {
+            Ok(response) => response,
+            Err(e) => return self.fail(id, format!("{:#}", e)),
+        };
+
         // Check content type to determine how to parse the response
         let content_type = response
             .headers()
@@ -106,119 +264,403 @@ impl HttpTransport {
             .unwrap_or("")
             .to_string();
 
-        // Handle streaming responses (SSE or newline-delimited JSON)
-        if content_type.contains("text/event-stream") || content_type.contains("application/x-ndjson") {
-            // For streaming, read the response as text and parse the first JSON-RPC message
-            let text = response.text().await.context("Failed to read response text")?;
-            
-            // Note: For debugging, you can uncomment the following lines:
-            // eprintln!("DEBUG: Content-Type: {}", content_type);
-            // eprintln!("DEBUG: Response preview (first 500 chars): {}", 
-            //          if text.len() > 500 { &text[..500] } else { &text });
-            
-            // For SSE, extract data lines (format: "data: {...}\n")
-            // For newline-delimited JSON, each line is a JSON object
-            let json_text = if text.contains("data:") {
-                // SSE format - extract JSON from "data: {...}" lines
-                text.lines()
-                    .find_map(|line| {
-                        let trimmed = line.trim();
-                        if trimmed.starts_with("data:") {
-                            let data = trimmed.strip_prefix("data:").unwrap_or("").trim();
-                            if !data.is_empty() && data != "[DONE]" {
-                                Some(data)
-                            } else {
-                                None
-                            }
-                        } else {
-                            None
-                        }
-                    })
-                    .unwrap_or("")
-            } else {
-                // Newline-delimited JSON - take the first non-empty line
-                text.lines()
-                    .find(|line| !line.trim().is_empty())
-                    .unwrap_or("")
-            };
-
-            if json_text.is_empty() {
-                anyhow::bail!("No JSON data found in streaming response. Raw response: {}", 
-                             if text.len() > 200 { &text[..200] } else { &text });
-            }
+        if content_type.contains("text/event-stream") {
+            return self.run_response(response, id).await;
+        }
 
-            let response_json: Value = serde_json::from_str(json_text)
-                .with_context(|| format!("Failed to parse JSON from streaming response. JSON text: {}", json_text))?;
+        if let Err(e) = self.forward_body(response, &content_type).await {
+            self.fail(id, format!("{:#}", e));
+        }
+    }
 
-            let jsonrpc_response: JsonRpcResponse = serde_json::from_value(response_json)
-                .context("Failed to deserialize JSON-RPC response")?;
+    /// Forward the messages of a JSON or newline-delimited JSON response body
+    async fn forward_body(&self, response: Response, content_type: &str) -> Result<()> {
+        let text = response.text().await.context("Failed to read response text")?;
 
-            Ok(Some(jsonrpc_response))
+        let payloads: Vec<&str> = if content_type.contains("application/x-ndjson") {
+            // Newline-delimited JSON - each non-empty line is a message
+            text.lines().filter(|line| !line.trim().is_empty()).collect()
         } else {
             // Standard JSON response
-            let response_json: Value = response
-                .json()
-                .await
-                .context("Failed to parse HTTP response as JSON")?;
+            vec![text.as_str()]
+        };
 
-            let jsonrpc_response: JsonRpcResponse = serde_json::from_value(response_json)
-                .context("Failed to deserialize JSON-RPC response")?;
-
-            Ok(Some(jsonrpc_response))
+        for payload in payloads {
+            let message: JsonRpcMessage = serde_json::from_str(payload)
+                .with_context(|| format!("Failed to parse JSON-RPC message from response: {}", payload))?;
+            // The transport was dropped if this fails; nobody is waiting
+            let _ = self.incoming.send(message);
         }
+
+        Ok(())
     }
 
-    /// Send a request and wait for a response
+    /// Queue an error response for request `id`
+    fn fail(&self, id: Value, message: String) {
+        warn!(id = %id, error = %message, "MCP request over HTTP failed");
+        let error = JsonRpcError::internal_error(Some(Value::String(message)));
+        let _ = self
+            .incoming
+            .send(JsonRpcMessage::Response(JsonRpcResponse::error(id, error)));
+    }
+
+    /// Read the SSE stream answering request `id`, resuming it if it drops early
     ///
-    /// This is a convenience method that wraps send() and extracts the result.
-    pub async fn request(
-        &mut self,
-        method: impl Into<String>,
-        params: Option<Value>,
-    ) -> Result<Value> {
-        let id = Value::Number(serde_json::Number::from(self.next_id));
-        self.next_id += 1;
-        
-        let request = JsonRpcRequest::new(id.clone(), method, params);
-        let message = JsonRpcMessage::Request(request);
-
-        let response = self.send(&message).await?;
-
-        match response {
-            Some(resp) => {
-                // Verify the response ID matches
-                if resp.id != id {
-                    anyhow::bail!(
-                        "Response ID mismatch: expected {:?}, got {:?}",
-                        id,
-                        resp.id
-                    );
+    /// If the response never arrives, an error response is queued so the
+    /// pending request fails instead of waiting forever.
+    async fn run_response(mut self, response: Response, id: Value) {
+        let mut response = Some(response);
+        let mut attempts = 0;
+
+        loop {
+            if let Some(response) = response.take() {
+                match self.forward(response, Some(&id)).await {
+                    Ok(true) => return,
+                    Ok(false) => debug!(id = %id, "SSE stream ended before the response"),
+                    Err(e) => debug!(id = %id, error = %e, "SSE stream interrupted"),
                 }
-                
-                match resp.result_or_error {
-                    crate::mcp::types::ResultOrError::Success { result } => Ok(result),
-                    crate::mcp::types::ResultOrError::Error { error } => {
-                        anyhow::bail!("JSON-RPC error: {} (code: {})", error.message, error.code)
+            }
+
+            // Resumption is only possible if the server numbered its events
+            if self.parser.last_event_id.is_none() || attempts >= MAX_RESUME_ATTEMPTS {
+                break;
+            }
+            attempts += 1;
+            tokio::time::sleep(self.parser.retry.unwrap_or(DEFAULT_RETRY)).await;
+
+            match self.open().await {
+                Ok(Some(resumed)) => response = Some(resumed),
+                Ok(None) => break,
+                Err(e) => debug!(id = %id, error = %e, "Failed to resume SSE stream"),
+            }
+        }
+
+        self.fail(id, "SSE stream closed without a response".to_string());
+    }
+
+    /// Keep the standalone GET stream open until the transport goes away
+    async fn run_standalone(mut self) {
+        let mut backoff = DEFAULT_RETRY;
+
+        while !self.incoming.is_closed() {
+            match self.open().await {
+                Ok(Some(response)) => {
+                    backoff = self.parser.retry.unwrap_or(DEFAULT_RETRY);
+                    if let Err(e) = self.forward(response, None).await {
+                        debug!(error = %e, "Standalone SSE stream interrupted");
                     }
                 }
+                Ok(None) => {
+                    debug!("Server does not offer a standalone SSE stream");
+                    return;
+                }
+                Err(e) => {
+                    debug!(error = %e, "Failed to open standalone SSE stream");
+                    backoff = (backoff * 2).min(MAX_RETRY);
+                }
             }
-            None => {
-                anyhow::bail!("Expected response but received none")
+
+            tokio::time::sleep(backoff).await;
+        }
+    }
+
+    /// Open a GET stream, resuming after the last received event if any
+    ///
+    /// Returns `None` if the server doesn't support GET streams (405).
+    async fn open(&self) -> Result<Option<Response>> {
+        let mut request_builder = self
+            .client
+            .get(&self.server_url)
+            .header("Accept", "text/event-stream");
+
+        let session_id = self.session_id.lock().unwrap().clone();
+        if let Some(session_id) = session_id {
+            request_builder = request_builder.header("Mcp-Session-Id", session_id);
+        }
+        if let Some(last_event_id) = &self.parser.last_event_id {
+            request_builder = request_builder.header("Last-Event-ID", last_event_id);
+        }
+
+        let response = request_builder
+            .send()
+            .await
+            .context("Failed to open SSE stream")?;
+
+        match response.status() {
+            StatusCode::METHOD_NOT_ALLOWED => Ok(None),
+            status if status.is_success() => Ok(Some(response)),
+            status => anyhow::bail!("SSE stream request failed with status {}", status),
+        }
+    }
+
+    /// Forward every message in `response` until the stream ends
+    ///
+    /// Returns `true` once the response to `id` has been forwarded; the
+    /// server closes the stream after it, so there is nothing left to read.
+    async fn forward(&mut self, response: Response, id: Option<&Value>) -> Result<bool> {
+        let mut body = response.bytes_stream();
+
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk.context("Failed to read SSE stream")?;
+
+            for data in self.parser.feed(&chunk) {
+                let message: JsonRpcMessage = match serde_json::from_str(&data) {
+                    Ok(message) => message,
+                    Err(e) => {
+                        warn!(error = %e, "Ignoring malformed SSE message");
+                        continue;
+                    }
+                };
+
+                let answers_request = matches!(
+                    (&message, id),
+                    (JsonRpcMessage::Response(response), Some(id)) if response.id == *id
+                );
+                if self.incoming.send(message).is_err() {
+                    // The transport was dropped
+                    return Ok(true);
+                }
+                if answers_request {
+                    return Ok(true);
+                }
             }
         }
+
+        Ok(false)
     }
+}
 
-    /// Send a notification (no response expected)
-    pub async fn notify(&mut self, method: impl Into<String>, params: Option<Value>) -> Result<()> {
-        let notification = JsonRpcRequest::notification(method, params);
-        let message = JsonRpcMessage::Request(notification);
-        self.send(&message).await?;
-        Ok(())
+/// Incremental parser for `text/event-stream` bodies
+///
+/// Bytes may be fed in arbitrary chunks; complete events are returned as soon
+/// as their terminating blank line arrives.
+#[derive(Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+    data: Vec<String>,
+    event: Option<String>,
+    /// ID of the last event received, sent as `Last-Event-ID` when resuming
+    last_event_id: Option<String>,
+    /// Reconnection delay requested by the server
+    retry: Option<Duration>,
+}
+
+impl SseParser {
+    /// Feed a chunk of the body, returning the data of each completed message event
+    fn feed(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                if let Some(data) = self.dispatch() {
+                    events.push(data);
+                }
+                continue;
+            }
+
+            // Lines starting with ':' are comments, used as keep-alives
+            if line.starts_with(':') {
+                continue;
+            }
+
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                None => (line, ""),
+            };
+            match field {
+                "data" => self.data.push(value.to_string()),
+                "event" => self.event = Some(value.to_string()),
+                "id" if !value.contains('\0') => self.last_event_id = Some(value.to_string()),
+                "retry" => {
+                    if let Ok(ms) = value.parse() {
+                        self.retry = Some(Duration::from_millis(ms));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        events
     }
 
-    /// Get the server URL
-    pub fn server_url(&self) -> &str {
-        &self.server_url
+    /// Complete the current event, returning its data if it carries a message
+    fn dispatch(&mut self) -> Option<String> {
+        let event = self.event.take();
+        if self.data.is_empty() {
+            return None;
+        }
+        let data = std::mem::take(&mut self.data).join("\n");
+
+        match event.as_deref() {
+            None | Some("message") => Some(data),
+            Some(other) => {
+                debug!(event = %other, "Ignoring SSE event");
+                None
+            }
+        }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{header, HeaderMap};
+    use axum::routing::post;
+    use axum::Router;
+    use serde_json::json;
+
+    #[test]
+    fn test_sse_parser_split_chunks() {
+        let mut parser = SseParser::default();
+
+        assert!(parser.feed(b"id: 1\r\ndata: {\"a\":").is_empty());
+        let events = parser.feed(b"1}\r\n\r\n: keep-alive\n\ndata: first\ndata: second\n\n");
+
+        assert_eq!(events, vec![r#"{"a":1}"#, "first\nsecond"]);
+        assert_eq!(parser.last_event_id.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_sse_parser_fields() {
+        let mut parser = SseParser::default();
+        let events = parser.feed(b"retry: 2500\nevent: ping\ndata: ignored\n\nevent: message\ndata: kept\n\n");
+
+        assert_eq!(events, vec!["kept"]);
+        assert_eq!(parser.retry, Some(Duration::from_millis(2500)));
+    }
+
+    fn event_stream(body: String) -> ([(header::HeaderName, &'static str); 1], String) {
+        ([(header::CONTENT_TYPE, "text/event-stream")], body)
+    }
+
+    /// Server whose POST stream drops after a progress notification; the
+    /// response is only delivered when the stream is resumed via GET
+    async fn spawn_server() -> String {
+        let post_stream = || async {
+            let progress = json!({"jsonrpc": "2.0", "method": "notifications/progress", "params": {"progress": 1}});
+            event_stream(format!("retry: 10\nid: 1\ndata: {}\n\n", progress))
+        };
+        let resume_stream = |headers: HeaderMap| async move {
+            let last_event_id = headers.get("last-event-id").and_then(|v| v.to_str().ok());
+            assert_eq!(last_event_id, Some("1"));
+            let response = json!({"jsonrpc": "2.0", "id": 7, "result": {"done": true}});
+            event_stream(format!("id: 2\ndata: {}\n\n", response))
+        };
+
+        let app = Router::new().route("/mcp", post(post_stream).get(resume_stream));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        format!("http://{}/mcp", addr)
+    }
+
+    #[tokio::test]
+    async fn test_sse_stream_resumed_with_last_event_id() {
+        let url = spawn_server().await;
+        let mut transport = HttpTransport::new(url).with_standalone_stream(false);
+
+        let request = JsonRpcRequest::new(json!(7), "tools/call", None);
+        transport.send(&JsonRpcMessage::Request(request)).await.unwrap();
+
+        match transport.receive().await.unwrap() {
+            JsonRpcMessage::Request(notification) => {
+                assert_eq!(notification.method(), "notifications/progress")
+            }
+            other => panic!("Expected progress notification, got {:?}", other),
+        }
+        match transport.receive().await.unwrap() {
+            JsonRpcMessage::Response(response) => assert_eq!(response.id, json!(7)),
+            other => panic!("Expected response, got {:?}", other),
+        }
+    }
+
+    /// JSON-mode server that answers `slow` only once `fast` has arrived
+    async fn spawn_json_server() -> String {
+        let arrived = Arc::new(tokio::sync::Notify::new());
+        let handler = move |axum::Json(request): axum::Json<Value>| {
+            let arrived = Arc::clone(&arrived);
+            async move {
+                if request["method"] == "slow" {
+                    arrived.notified().await;
+                } else {
+                    arrived.notify_one();
+                }
+                axum::Json(json!({"jsonrpc": "2.0", "id": request["id"], "result": {}}))
+            }
+        };
+
+        let app = Router::new().route("/mcp", post(handler));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        format!("http://{}/mcp", addr)
+    }
+
+    #[tokio::test]
+    async fn test_json_requests_do_not_block_each_other() {
+        let url = spawn_json_server().await;
+        let mut transport = HttpTransport::new(url).with_standalone_stream(false);
+
+        for (id, method) in [(1, "slow"), (2, "fast")] {
+            let request = JsonRpcRequest::new(json!(id), method, None);
+            transport.send(&JsonRpcMessage::Request(request)).await.unwrap();
+        }
+
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            match transport.receive().await.unwrap() {
+                JsonRpcMessage::Response(response) => ids.push(response.id),
+                other => panic!("Expected response, got {:?}", other),
+            }
+        }
+        assert_eq!(ids, vec![json!(2), json!(1)]);
+    }
+
+    #[tokio::test]
+    async fn test_hung_request_times_out_without_blocking_the_client() {
+        use crate::mcp::client::{McpClient, RequestTimeout};
+        use axum::http::StatusCode;
+        use axum::response::IntoResponse;
+
+        // Never answers `hang`, and records cancellations
+        let cancelled = Arc::new(Mutex::new(Vec::new()));
+        let handler = {
+            let cancelled = Arc::clone(&cancelled);
+            move |axum::Json(request): axum::Json<Value>| {
+                let cancelled = Arc::clone(&cancelled);
+                async move {
+                    match request["method"].as_str() {
+                        Some("hang") => std::future::pending::<axum::response::Response>().await,
+                        Some("notifications/cancelled") => {
+                            cancelled.lock().unwrap().push(request["params"]["requestId"].clone());
+                            StatusCode::ACCEPTED.into_response()
+                        }
+                        _ => axum::Json(json!({"jsonrpc": "2.0", "id": request["id"], "result": {"ok": true}}))
+                            .into_response(),
+                    }
+                }
+            }
+        };
+        let app = Router::new().route("/mcp", post(handler));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let transport = HttpTransport::new(format!("http://{}/mcp", addr)).with_standalone_stream(false);
+        let client = McpClient::new(transport).with_request_timeout(Some(Duration::from_millis(200)));
+
+        let error = client.request("hang", None).await.unwrap_err();
+        assert!(error.downcast_ref::<RequestTimeout>().is_some());
+
+        let result = client.request("ping", None).await.unwrap();
+        assert_eq!(result, json!({"ok": true}));
+        assert_eq!(cancelled.lock().unwrap().len(), 1);
+    }
+}
@@ -1,12 +1,41 @@
 //! Transport implementations for MCP
 //!
 //! This module contains different transport mechanisms for communicating
-//! with MCP servers.
+//! with MCP servers. All of them implement [`Transport`], so an
+//! [`McpClient`](crate::mcp::McpClient) works the same way over any of them.
 
-pub mod stdio;
 pub mod http;
+pub mod stdio;
+pub mod websocket;
+
+use crate::mcp::types::JsonRpcMessage;
+use anyhow::Result;
+use async_trait::async_trait;
+
+/// Common interface for MCP transports
+///
+/// A transport moves JSON-RPC messages between the client and one server.
+/// Messages from the server (responses, notifications and server-initiated
+/// requests) are all delivered through [`receive`](Transport::receive).
+#[async_trait]
+pub trait Transport: Send {
+    /// Send a JSON-RPC message to the server
+    async fn send(&mut self, message: &JsonRpcMessage) -> Result<()>;
+
+    /// Receive the next JSON-RPC message from the server
+    ///
+    /// Waits until a message is available. Must be cancel-safe: the client
+    /// polls it alongside outgoing messages, so dropping the future before it
+    /// completes must not lose any data.
+    async fn receive(&mut self) -> Result<JsonRpcMessage>;
 
-// Placeholder modules for future transports
-#[allow(dead_code)]
-mod websocket;
+    /// Check if the transport is still usable
+    fn is_alive(&mut self) -> bool {
+        true
+    }
 
+    /// Close the transport, releasing any connection or process it holds
+    async fn close(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -2,25 +2,53 @@
 //!
 //! Handles communication over stdin/stdout using newline-delimited JSON-RPC messages.
 //! used for local MCP servers
+//!
+//! The server's stderr is drained into `tracing` so a chatty server can't
+//! fill the pipe and stall. Closing the transport follows the MCP shutdown
+//! sequence: close stdin, give the server time to exit, then kill it.
 
+use super::Transport;
 use crate::mcp::types::JsonRpcMessage;
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use serde_json;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader as TokioBufReader};
 use tokio::process::{Child, Command};
+use tracing::{info, warn};
+
+/// Time the server gets to exit after its stdin is closed before it is killed
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
 
 /// Stdio transport for MCP communication
 pub struct StdioTransport {
-    stdin: tokio::process::ChildStdin,
+    /// `None` once the transport has been closed
+    stdin: Option<tokio::process::ChildStdin>,
     stdout: TokioBufReader<tokio::process::ChildStdout>,
+    /// Partially read line, kept across calls so `receive` is cancel-safe
+    line: Vec<u8>,
     child: Child,
 }
 
 impl StdioTransport {
     /// Create a new stdio transport by spawning an MCP server process
     pub fn spawn(command: &str, args: &[&str]) -> Result<Self> {
+        Self::spawn_with_env(command, args, &HashMap::new())
+    }
+
+    /// Spawn an MCP server process with extra environment variables
+    ///
+    /// The variables are added to the inherited environment.
+    pub fn spawn_with_env(
+        command: &str,
+        args: &[&str],
+        env: &HashMap<String, String>,
+    ) -> Result<Self> {
         let mut child = Command::new(command)
             .args(args)
+            .envs(env)
             .stdin(std::process::Stdio::piped())
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped())
@@ -36,28 +64,57 @@ impl StdioTransport {
             .stdout
             .take()
             .context("Failed to get stdout handle")?;
+        let stderr = child
+            .stderr
+            .take()
+            .context("Failed to get stderr handle")?;
+
+        // Servers log to stderr; forward it until the process exits
+        let server = Path::new(command)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| command.to_string());
+        tokio::spawn(async move {
+            let mut lines = TokioBufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                info!(server = %server, "{}", line);
+            }
+        });
 
         Ok(Self {
-            stdin,
+            stdin: Some(stdin),
             stdout: TokioBufReader::new(stdout),
+            line: Vec::new(),
             child,
         })
     }
 
+    /// Wait for the child process to exit
+    pub async fn wait(&mut self) -> Result<std::process::ExitStatus> {
+        self.child
+            .wait()
+            .await
+            .context("Failed to wait for child process")
+    }
+}
+
+#[async_trait]
+impl Transport for StdioTransport {
     /// Send a JSON-RPC message
-    pub async fn send(&mut self, message: &JsonRpcMessage) -> Result<()> {
+    async fn send(&mut self, message: &JsonRpcMessage) -> Result<()> {
         let json = serde_json::to_string(message)
             .context("Failed to serialize JSON-RPC message")?;
-        
-        self.stdin
+
+        let stdin = self.stdin.as_mut().context("Transport is closed")?;
+        stdin
             .write_all(json.as_bytes())
             .await
             .context("Failed to write to stdin")?;
-        self.stdin
+        stdin
             .write_all(b"\n")
             .await
             .context("Failed to write newline")?;
-        self.stdin
+        stdin
             .flush()
             .await
             .context("Failed to flush stdin")?;
@@ -66,43 +123,109 @@ impl StdioTransport {
     }
 
     /// Receive a JSON-RPC message
-    pub async fn receive(&mut self) -> Result<JsonRpcMessage> {
-        let mut line = String::new();
-        self.stdout
-            .read_line(&mut line)
-            .await
-            .context("Failed to read from stdout")?;
-
-        if line.is_empty() {
-            anyhow::bail!("EOF reached");
+    ///
+    /// Lines that aren't valid JSON-RPC are logged and skipped. Once stdout
+    /// reaches EOF, waits for the process to exit so that
+    /// [`is_alive`](Transport::is_alive) is accurate when this returns an error.
+    /// A process that hasn't exited within the shutdown grace period is killed.
+    async fn receive(&mut self) -> Result<JsonRpcMessage> {
+        loop {
+            // `read_until` keeps partial reads in the buffer if cancelled
+            self.stdout
+                .read_until(b'\n', &mut self.line)
+                .await
+                .context("Failed to read from stdout")?;
+
+            if self.line.is_empty() {
+                match tokio::time::timeout(SHUTDOWN_GRACE_PERIOD, self.child.wait()).await {
+                    Ok(status) => {
+                        let status = status.context("Failed to wait for child process")?;
+                        anyhow::bail!("MCP server exited ({})", status);
+                    }
+                    Err(_) => {
+                        warn!("MCP server closed stdout but did not exit, killing it");
+                        self.child.kill().await.context("Failed to kill child process")?;
+                        anyhow::bail!("MCP server closed stdout");
+                    }
+                }
+            }
+
+            let line = std::mem::take(&mut self.line);
+            let line = line.trim_ascii();
+            if line.is_empty() {
+                continue;
+            }
+
+            match serde_json::from_slice(line) {
+                Ok(message) => return Ok(message),
+                Err(e) => warn!(
+                    error = %e,
+                    line = %String::from_utf8_lossy(line),
+                    "Ignoring invalid message from MCP server"
+                ),
+            }
         }
-
-        // Trim newline
-        let line = line.trim_end();
-
-        let message: JsonRpcMessage = serde_json::from_str(line)
-            .context("Failed to parse JSON-RPC message")?;
-
-        Ok(message)
     }
 
     /// Check if the child process is still running
-    pub fn is_alive(&mut self) -> bool {
+    fn is_alive(&mut self) -> bool {
         self.child.try_wait().map(|s| s.is_none()).unwrap_or(false)
     }
 
-    /// Wait for the child process to exit
-    pub async fn wait(&mut self) -> Result<std::process::ExitStatus> {
-        self.child
-            .wait()
-            .await
-            .context("Failed to wait for child process")
+    /// Shut the server down: close stdin, wait for it to exit, then kill it
+    async fn close(&mut self) -> Result<()> {
+        // Dropping stdin closes the pipe, which tells the server to exit
+        self.stdin.take();
+
+        match tokio::time::timeout(SHUTDOWN_GRACE_PERIOD, self.child.wait()).await {
+            Ok(status) => {
+                status.context("Failed to wait for child process")?;
+                Ok(())
+            }
+            Err(_) => {
+                warn!("MCP server did not exit after stdin was closed, killing it");
+                self.child
+                    .kill()
+                    .await
+                    .context("Failed to kill child process")
+            }
+        }
     }
 }
 
-impl Drop for StdioTransport {
-    fn drop(&mut self) {
-        let _ = self.child.wait();
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_close_waits_for_exit() {
+        // `cat` exits as soon as its stdin is closed
+        let mut transport = StdioTransport::spawn("cat", &[]).unwrap();
+        assert!(transport.is_alive());
+
+        transport.close().await.unwrap();
+        assert!(!transport.is_alive());
     }
-}
 
+    #[tokio::test]
+    async fn test_receive_skips_invalid_lines_and_reports_exit() {
+        let script = r#"echo 'not json'; echo '{"jsonrpc":"2.0","method":"ping"}'; echo oops >&2"#;
+        let mut transport = StdioTransport::spawn("sh", &["-c", script]).unwrap();
+
+        let message = transport.receive().await.unwrap();
+        assert!(matches!(message, JsonRpcMessage::Request(request) if request.method() == "ping"));
+
+        let error = transport.receive().await.unwrap_err();
+        assert!(error.to_string().contains("exited"));
+        assert!(!transport.is_alive());
+    }
+
+    #[tokio::test]
+    async fn test_receive_kills_server_that_closed_stdout() {
+        let mut transport = StdioTransport::spawn("sh", &["-c", "exec >&-; sleep 60"]).unwrap();
+
+        let error = transport.receive().await.unwrap_err();
+        assert!(error.to_string().contains("closed stdout"));
+        assert!(!transport.is_alive());
+    }
+}
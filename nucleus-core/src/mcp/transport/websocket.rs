@@ -0,0 +1,146 @@
+//! WebSocket transport for MCP
+//!
+//! Handles communication over a single WebSocket connection, one JSON-RPC
+//! message per text frame. Suited to long-lived MCP servers running as local
+//! daemons.
+
+use super::Transport;
+use crate::mcp::types::JsonRpcMessage;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{HeaderName, HeaderValue};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tracing::warn;
+
+/// WebSocket transport for MCP communication
+pub struct WebSocketTransport {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    closed: bool,
+}
+
+impl WebSocketTransport {
+    /// Connect to an MCP server at a `ws://` or `wss://` URL
+    pub async fn connect(url: &str) -> Result<Self> {
+        Self::connect_with_headers(url, &HashMap::new()).await
+    }
+
+    /// Connect with extra headers on the handshake request, e.g. `Authorization`
+    pub async fn connect_with_headers(url: &str, headers: &HashMap<String, String>) -> Result<Self> {
+        let mut request = url
+            .into_client_request()
+            .with_context(|| format!("Invalid WebSocket URL: {}", url))?;
+        for (name, value) in headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .with_context(|| format!("Invalid header name: {}", name))?;
+            let value = HeaderValue::from_str(value)
+                .with_context(|| format!("Invalid value for header {}", name))?;
+            request.headers_mut().insert(name, value);
+        }
+
+        let (stream, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .with_context(|| format!("Failed to connect to WebSocket server at {}", url))?;
+
+        Ok(Self {
+            stream,
+            closed: false,
+        })
+    }
+}
+
+#[async_trait]
+impl Transport for WebSocketTransport {
+    /// Send a JSON-RPC message as a text frame
+    async fn send(&mut self, message: &JsonRpcMessage) -> Result<()> {
+        let json = serde_json::to_string(message)
+            .context("Failed to serialize JSON-RPC message")?;
+
+        self.stream
+            .send(Message::text(json))
+            .await
+            .context("Failed to send WebSocket message")
+    }
+
+    /// Receive the next JSON-RPC message, skipping control frames
+    ///
+    /// Frames that aren't valid JSON-RPC are logged and skipped.
+    async fn receive(&mut self) -> Result<JsonRpcMessage> {
+        loop {
+            let frame = match self.stream.next().await {
+                Some(frame) => frame.context("Failed to read from WebSocket")?,
+                None => {
+                    self.closed = true;
+                    anyhow::bail!("WebSocket connection closed");
+                }
+            };
+
+            let payload = match frame {
+                Message::Text(text) => text.as_bytes().to_vec(),
+                Message::Binary(data) => data.to_vec(),
+                Message::Close(_) => {
+                    self.closed = true;
+                    anyhow::bail!("WebSocket connection closed by server");
+                }
+                // Pings are answered automatically; nothing else carries messages
+                _ => continue,
+            };
+
+            match serde_json::from_slice(&payload) {
+                Ok(message) => return Ok(message),
+                Err(e) => warn!(
+                    error = %e,
+                    frame = %String::from_utf8_lossy(&payload),
+                    "Ignoring invalid message from MCP server"
+                ),
+            }
+        }
+    }
+
+    fn is_alive(&mut self) -> bool {
+        !self.closed
+    }
+
+    /// Send a close frame and wait for the connection to shut down
+    async fn close(&mut self) -> Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+        self.stream
+            .close(None)
+            .await
+            .context("Failed to close WebSocket connection")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_receive_skips_invalid_frames() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            ws.send(Message::text("not json")).await.unwrap();
+            ws.send(Message::text(r#"{"jsonrpc":"2.0","method":"ping"}"#)).await.unwrap();
+            ws.close(None).await.unwrap();
+        });
+
+        let mut transport = WebSocketTransport::connect(&format!("ws://{}", addr)).await.unwrap();
+
+        let message = transport.receive().await.unwrap();
+        assert!(matches!(message, JsonRpcMessage::Request(request) if request.method() == "ping"));
+
+        let error = transport.receive().await.unwrap_err();
+        assert!(error.to_string().contains("closed"));
+        assert!(!transport.is_alive());
+    }
+}
@@ -0,0 +1,187 @@
+//! Creating the provider selected in the configuration.
+//!
+//! [`ProviderFactory`] builds the provider named by `llm.provider`, and the
+//! embedding provider named by `rag.provider` when it differs. The built-in
+//! providers are always available; applications can register constructors
+//! for their own providers under a name, which the config then selects like
+//! any other provider.
+
+use super::types::{Provider, ProviderError, Result};
+use super::{MistralRsProvider, OllamaProvider, OpenAiCompatProvider};
+use crate::config::{Config, ProviderKind};
+use futures::future::{BoxFuture, FutureExt};
+use nucleus_plugin::PluginRegistry;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+
+type Constructor = Arc<dyn Fn(Config, Arc<PluginRegistry>) -> BoxFuture<'static, Result<Arc<dyn Provider>>> + Send + Sync>;
+
+/// Creates providers from `llm.provider`.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use nucleus_core::provider::{OllamaProvider, Provider, ProviderFactory};
+/// # use nucleus_core::{ChatManager, Config};
+/// # use nucleus_plugin::{PluginRegistry, Permission};
+/// # use std::sync::Arc;
+/// # async fn example() -> anyhow::Result<()> {
+/// let mut factory = ProviderFactory::new();
+/// factory.register("my-backend", |config: Config, _registry| async move {
+///     // Construct your own `Provider` implementation here
+///     Ok(Arc::new(OllamaProvider::new(&config)) as Arc<dyn Provider>)
+/// });
+///
+/// // With `provider: my-backend` under `llm` in config.yaml
+/// let manager = ChatManager::builder(Config::load_or_default(), PluginRegistry::new(Permission::READ_ONLY))
+///     .with_provider_factory(factory)
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct ProviderFactory {
+    custom: HashMap<String, Constructor>,
+}
+
+impl ProviderFactory {
+    /// Creates a factory with only the built-in providers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a constructor for the custom provider `name`.
+    ///
+    /// The constructor receives the configuration and the plugin registry,
+    /// for providers that need the tools up front. Registering a name again
+    /// replaces the previous constructor.
+    pub fn register<F, Fut>(&mut self, name: impl Into<String>, constructor: F)
+    where
+        F: Fn(Config, Arc<PluginRegistry>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Arc<dyn Provider>>> + Send + 'static,
+    {
+        self.custom.insert(
+            name.into(),
+            Arc::new(move |config, registry| constructor(config, registry).boxed()),
+        );
+    }
+
+    /// Names of the registered custom providers, sorted.
+    pub fn custom_providers(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.custom.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Creates the provider selected by `config.llm.provider`, or the
+    /// mistral.rs provider if it isn't set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the provider fails to initialize, or if
+    /// `llm.provider` names a custom provider that isn't registered.
+    pub async fn create(&self, config: &Config, registry: Arc<PluginRegistry>) -> Result<Arc<dyn Provider>> {
+        let provider: Arc<dyn Provider> = match config.llm.provider.as_ref().unwrap_or(&ProviderKind::MistralRs) {
+            ProviderKind::MistralRs => Arc::new(MistralRsProvider::new(config, registry).await?),
+            ProviderKind::Ollama => Arc::new(OllamaProvider::new(config)),
+            ProviderKind::OpenAiCompatible => Arc::new(OpenAiCompatProvider::new(config)),
+            ProviderKind::Custom(name) => {
+                let constructor = self.custom.get(name).ok_or_else(|| {
+                    ProviderError::UnknownProvider(format!(
+                        "{} (registered custom providers: {})",
+                        name,
+                        self.custom_providers().join(", ")
+                    ))
+                })?;
+                constructor(config.clone(), registry).await?
+            }
+        };
+
+        Ok(provider)
+    }
+
+    /// Creates the embedding provider selected by `config.rag.provider`.
+    ///
+    /// Returns `None` if `rag.provider` isn't set, in which case the chat
+    /// provider should compute embeddings as well. `rag.base_url` and
+    /// `rag.api_key` take the place of their `llm` counterparts.
+    pub async fn create_embedding(
+        &self,
+        config: &Config,
+        registry: Arc<PluginRegistry>,
+    ) -> Result<Option<Arc<dyn Provider>>> {
+        let Some(kind) = &config.rag.provider else {
+            return Ok(None);
+        };
+
+        // Providers read their connection settings from `llm`
+        let mut config = config.clone();
+        config.llm.provider = Some(kind.clone());
+        if let Some(base_url) = &config.rag.base_url {
+            config.llm.base_url = base_url.clone();
+        }
+        if let Some(api_key) = &config.rag.api_key {
+            config.llm.api_key = Some(api_key.clone());
+        }
+
+        let provider: Arc<dyn Provider> = match kind {
+            // Don't load a chat model just to compute embeddings
+            ProviderKind::MistralRs => Arc::new(MistralRsProvider::for_embeddings(&config, registry)),
+            _ => self.create(&config, registry).await?,
+        };
+
+        Ok(Some(provider))
+    }
+}
+
+impl fmt::Debug for ProviderFactory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProviderFactory")
+            .field("custom", &self.custom_providers())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nucleus_plugin::Permission;
+
+    fn registry() -> Arc<PluginRegistry> {
+        Arc::new(PluginRegistry::new(Permission::NONE))
+    }
+
+    #[tokio::test]
+    async fn test_custom_provider_by_name() {
+        let mut factory = ProviderFactory::new();
+        factory.register("remote", |mut config: Config, _registry| async move {
+            config.llm.base_url = "http://remote:11434".to_string();
+            Ok(Arc::new(OllamaProvider::new(&config)) as Arc<dyn Provider>)
+        });
+
+        let mut config = Config::default();
+        config.llm.provider = Some(ProviderKind::Custom("remote".to_string()));
+        assert!(factory.create(&config, registry()).await.is_ok());
+
+        config.llm.provider = Some(ProviderKind::Custom("missing".to_string()));
+        let error = factory.create(&config, registry()).await.err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Unknown provider: missing (registered custom providers: remote)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_embedding_provider_is_optional() {
+        let factory = ProviderFactory::new();
+        let mut config = Config::default();
+        assert!(factory.create_embedding(&config, registry()).await.unwrap().is_none());
+
+        config.rag.provider = Some(ProviderKind::Ollama);
+        config.rag.base_url = Some("http://embeddings:11434".to_string());
+        assert!(factory.create_embedding(&config, registry()).await.unwrap().is_some());
+    }
+}
@@ -6,18 +6,22 @@
 use crate::models::EmbeddingModel;
 use crate::Config;
 
+use super::{ThinkDelta, ThinkSplitter};
 use super::types::*;
 use anyhow::Context;
 use async_trait::async_trait;
+use either::Either;
 use mistralrs::{
-    EmbeddingModelBuilder, Function, GgufModelBuilder, IsqType, Model, PagedAttentionMetaBuilder, RequestBuilder, Response, TextMessageRole, TextMessages, TextModelBuilder, Tool as MistralTool, ToolChoice, ToolType
+    CalledFunction, EmbeddingModelBuilder, Function, GgufModelBuilder, IsqType, Model, PagedAttentionMetaBuilder, RequestBuilder, Response, StopTokens, TextMessageRole, TextModelBuilder, Tool as MistralTool, ToolCallResponse, ToolCallType, ToolChoice, ToolType
 };
 use nucleus_plugin::PluginRegistry;
 use tracing::{debug, info, warn};
 
+use std::collections::HashMap;
 use std::path::Path;
 use std::sync::Arc;
-use tokio::sync::OnceCell;
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tokio::sync::{Mutex, OnceCell};
 
 /// mistral.rs in-process provider.
 ///
@@ -27,11 +31,13 @@ use tokio::sync::OnceCell;
 ///
 /// Note: Use async `new()` - model loading requires async operations.
 pub struct MistralRsProvider {
-    model: Arc<Model>,
+    /// Chat model, loaded by `new` or on first use
+    model: OnceCell<Arc<Model>>,
     model_name: String,
     registry: Arc<PluginRegistry>,
     config: Config,
-    embedding_model: OnceCell<Arc<Model>>,
+    /// Embedding models loaded so far, by model id
+    embedding_models: Mutex<HashMap<String, Arc<Model>>>,
 }
 
 impl MistralRsProvider {
@@ -73,14 +79,70 @@ impl MistralRsProvider {
         let model = Self::build_model(config.clone(), Arc::clone(&registry)).await?;
 
         Ok(Self {
-            model: Arc::new(model),
+            model: OnceCell::new_with(Some(Arc::new(model))),
             model_name,
             registry,
             config: config.clone(),
-            embedding_model: OnceCell::new(),
+            embedding_models: Mutex::new(HashMap::new()),
         })
     }
 
+    /// Creates a provider for embeddings only.
+    ///
+    /// Unlike [`new`](Self::new), this doesn't load the chat model; it is
+    /// loaded if `chat` is ever called.
+    pub fn for_embeddings(config: &Config, registry: Arc<PluginRegistry>) -> Self {
+        Self {
+            model: OnceCell::new(),
+            model_name: config.llm.model.clone(),
+            registry,
+            config: config.clone(),
+            embedding_models: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the chat model, loading it if needed.
+    async fn chat_model(&self) -> Result<&Arc<Model>> {
+        self.model
+            .get_or_try_init(|| async {
+                let model = Self::build_model(self.config.clone(), Arc::clone(&self.registry)).await?;
+                Ok::<Arc<Model>, ProviderError>(Arc::new(model))
+            })
+            .await
+    }
+
+    /// Returns the embedding model for `model`, loading it on first use.
+    async fn embedding_model(&self, model: &EmbeddingModel) -> Result<Arc<Model>> {
+        let mut models = self.embedding_models.lock().await;
+        if let Some(loaded) = models.get(&model.id) {
+            return Ok(Arc::clone(loaded));
+        }
+
+        let model_path: String = match &model.path {
+            Some(path) => path.to_string_lossy().into(),
+            None => model.hf_repo.clone().unwrap_or("Nucleus Registry".to_string())
+        };
+
+        info!("Loading embedding model from: {}", model_path);
+        
+        let loaded = EmbeddingModelBuilder::new(model_path.clone())
+            .with_logging()
+            .with_throughput_logging()
+            .with_token_source(mistralrs::TokenSource::None)
+            .build()
+            .await
+            .map_err(|e| {
+                ProviderError::Other(
+                    format!("Failed to load embedding model from '{}': {:?}\n\n\
+                        Make sure the model exists at that path.", model_path, e)
+                )
+            })?;
+
+        let loaded = Arc::new(loaded);
+        models.insert(model.id.clone(), Arc::clone(&loaded));
+        Ok(loaded)
+    }
+
     async fn build_model(config: Config, registry: Arc<PluginRegistry>) -> Result<Model> {
         let model_name = config.llm.model;
 
@@ -170,8 +232,8 @@ impl Provider for MistralRsProvider {
         request: ChatRequest,
         mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
     ) -> Result<()> {
-        // Build messages using TextMessages builder
-        let mut messages = TextMessages::new();
+        // Build messages, keeping tool calls and the ids linking results to them
+        let mut builder = RequestBuilder::new();
         
         for msg in &request.messages {
             let role = match msg.role.as_str() {
@@ -182,12 +244,30 @@ impl Provider for MistralRsProvider {
                 _ => TextMessageRole::User,
             };
             
-            messages = messages.add_message(role, &msg.content);
+            builder = match (&msg.tool_calls, &msg.tool_call_id) {
+                (Some(tool_calls), _) => {
+                    let tool_calls = tool_calls
+                        .iter()
+                        .enumerate()
+                        .map(|(index, tc)| ToolCallResponse {
+                            index,
+                            id: tc.id.clone().unwrap_or_default(),
+                            tp: ToolCallType::Function,
+                            function: CalledFunction {
+                                name: tc.function.name.clone(),
+                                arguments: tc.function.arguments.to_string(),
+                            },
+                        })
+                        .collect();
+                    builder.add_message_with_tool_call(role, &msg.content, tool_calls)
+                }
+                (None, Some(tool_call_id)) if msg.role == "tool" => {
+                    builder.add_tool_message(&msg.content, tool_call_id)
+                }
+                _ => builder.add_message(role, &msg.content),
+            };
         }
 
-        // Convert to RequestBuilder
-        let mut builder = RequestBuilder::from(messages);
-
         // Convert plugins to mistral.rs tool definitions
         // Tool calls are returned in the response for nucleus to execute
         if self.registry.get_count() > 0 {
@@ -240,11 +320,47 @@ impl Provider for MistralRsProvider {
             builder = builder.set_tools(mistral_tools).set_tool_choice(ToolChoice::Auto);
         }
 
+        // mistral.rs has no per-request seed or repetition penalty
+        let sampling = &request.sampling;
+        builder = builder.set_sampler_temperature(request.temperature);
+        if let Some(top_p) = sampling.top_p {
+            builder = builder.set_sampler_topp(top_p);
+        }
+        if let Some(top_k) = sampling.top_k {
+            builder = builder.set_sampler_topk(top_k);
+        }
+        if let Some(min_p) = sampling.min_p {
+            builder = builder.set_sampler_minp(min_p);
+        }
+        if let Some(penalty) = sampling.frequency_penalty {
+            builder = builder.set_sampler_frequency_penalty(penalty as f32);
+        }
+        if let Some(penalty) = sampling.presence_penalty {
+            builder = builder.set_sampler_presence_penalty(penalty as f32);
+        }
+        if let Some(max_tokens) = sampling.max_tokens {
+            builder = builder.set_sampler_max_len(max_tokens);
+        }
+        if !sampling.stop.is_empty() {
+            builder = builder.set_sampler_stop_toks(StopTokens::Seqs(sampling.stop.clone()));
+        }
+        if sampling.seed.is_some() || sampling.repetition_penalty.is_some() {
+            debug!("Ignoring seed and repetition_penalty, which mistral.rs doesn't support per request");
+        }
+        if let Some(enable_thinking) = request.enable_thinking {
+            builder = builder.enable_thinking(enable_thinking);
+        }
+
+        // Without a token, nothing ever cancels the request
+        let cancel = request.cancel.clone().unwrap_or_default();
+
+        let model = self.chat_model().await?;
+
         // Stream request
         let timeout_duration = std::time::Duration::from_secs(60);
         let mut stream = tokio::time::timeout(
             timeout_duration,
-            self.model.stream_chat_request(builder)
+            model.stream_chat_request(builder)
         )
         .await
         .map_err(|_| {
@@ -256,13 +372,18 @@ impl Provider for MistralRsProvider {
         .map_err(|e| ProviderError::Other(format!("Failed to create stream: {:?}", e)))?;
         
         let mut accumulated_content = String::new();
+        let mut accumulated_reasoning = String::new();
+        // The chat template leaves `<think>` blocks in the content
+        let mut splitter = ThinkSplitter::default();
         let mut final_tool_calls = None;
+        let mut usage = None;
         let mut message_role = String::from("assistant"); // Default, will be updated from stream
 
         // Process stream chunks with timeout per chunk to avoid hangs
         let chunk_timeout = std::time::Duration::from_secs(30);
         loop {
-            let next_fut = stream.next();
+            // Dropping the stream on cancellation stops the generation
+            let next_fut = cancel.run_until_cancelled(stream.next());
             let chunk_opt = tokio::time::timeout(chunk_timeout, next_fut)
                 .await
                 .map_err(|_| {
@@ -270,7 +391,8 @@ impl Provider for MistralRsProvider {
                     ProviderError::Other(
                         format!("No response chunk received after {} seconds. Generation stalled.", chunk_timeout.as_secs())
                     )
-                })?;
+                })?
+                .ok_or(ProviderError::Cancelled)?;
             let Some(chunk) = chunk_opt else { break; };
             match chunk {
                 Response::Chunk(resp) => {
@@ -280,20 +402,28 @@ impl Provider for MistralRsProvider {
                         
                         // Stream content incrementally
                         if let Some(content) = &choice.delta.content {
-                            accumulated_content.push_str(content);
+                            let ThinkDelta { reasoning, content } = splitter.push(content);
+                            accumulated_content.push_str(&content);
+                            accumulated_reasoning.push_str(&reasoning);
                             
                             // Send incremental update to callback
                             callback(ChatResponse {
                                 model: self.model_name.clone(),
-                                content: content.clone(),
+                                content,
+                                reasoning,
                                 done: false,
                                 message: Message {
                                     role: message_role.clone(),
                                     content: accumulated_content.clone(),
+                                    reasoning: (!accumulated_reasoning.is_empty())
+                                        .then(|| accumulated_reasoning.clone()),
                                     context: None,
                                     images: None,
                                     tool_calls: None,
+                                    tool_call_id: None,
+                                    name: None,
                                 },
+                                usage: None,
                             });
                         }
                         
@@ -302,6 +432,7 @@ impl Provider for MistralRsProvider {
                             final_tool_calls = Some(
                                 tcs.iter()
                                     .map(|tc| super::types::ToolCall {
+                                        id: Some(tc.id.clone()),
                                         function: super::types::ToolCallFunction {
                                             name: tc.function.name.clone(),
                                             arguments: serde_json::from_str(&tc.function.arguments)
@@ -313,57 +444,47 @@ impl Provider for MistralRsProvider {
                         }
                     }
                 }
-                Response::Done(_) => {
+                Response::Done(done) => {
+                    usage = Some(Usage {
+                        prompt_tokens: done.usage.prompt_tokens,
+                        completion_tokens: done.usage.completion_tokens,
+                    });
                     break;
                 }
                 _ => {}
             }
         }
 
-        // Send final done=true message with captured role
+        // Send final done=true message with captured role. Its content is
+        // only text held back as a possible partial tag; everything else was
+        // sent incrementally.
+        let rest = splitter.finish();
+        accumulated_content.push_str(&rest.content);
+        accumulated_reasoning.push_str(&rest.reasoning);
         callback(ChatResponse {
             model: self.model_name.clone(),
-            content: accumulated_content.clone(),
+            content: rest.content,
+            reasoning: rest.reasoning,
             done: true,
             message: Message {
                 role: message_role,
                 content: accumulated_content,
+                reasoning: (!accumulated_reasoning.is_empty()).then_some(accumulated_reasoning),
                 context: None,
                 images: None,
                 tool_calls: final_tool_calls,
+                tool_call_id: None,
+                name: None,
             },
+            usage,
         });
 
         Ok(())
     }
 
-    async fn embed(&self, text: &str, _model: &EmbeddingModel) -> Result<Vec<f32>> {
+    async fn embed(&self, text: &str, model: &EmbeddingModel) -> Result<Vec<f32>> {
         // Lazy load embedding model on first use
-        let embedding_model = self.embedding_model
-            .get_or_try_init(|| async {
-                let model_path: String  = match &self.config.rag.embedding_model.path {
-                    Some(path) => path.to_string_lossy().into(),
-                    None => self.config.rag.embedding_model.hf_repo.clone().unwrap_or("Nucleus Registry".to_string())
-                };
-
-                info!("Loading embedding model from: {}", model_path);
-                
-                let model = EmbeddingModelBuilder::new(model_path.clone())
-                    .with_logging()
-                    .with_throughput_logging()
-                    .with_token_source(mistralrs::TokenSource::None)
-                    .build()
-                    .await
-                    .map_err(|e| {
-                        ProviderError::Other(
-                            format!("Failed to load embedding model from '{}': {:?}\n\n\
-                                Make sure the model exists at that path.", model_path, e)
-                        )
-                    })?;
-                
-                Ok::<Arc<Model>, ProviderError>(Arc::new(model))
-            })
-            .await?;
+        let embedding_model = self.embedding_model(model).await?;
         
         // Generate embedding
         let embedding = embedding_model
@@ -375,4 +496,31 @@ impl Provider for MistralRsProvider {
         
         Ok(embedding)
     }
+
+    /// Counts tokens with the chat model's tokenizer.
+    ///
+    /// Tokenizing goes through the model's async API, so this blocks the
+    /// current worker thread. Falls back to [`estimate_tokens`] before the
+    /// model is loaded, outside a multi-threaded runtime, or if tokenizing fails.
+    fn count_tokens(&self, text: &str) -> usize {
+        let Some(model) = self.model.get() else {
+            return estimate_tokens(text);
+        };
+        let multi_threaded = Handle::try_current()
+            .is_ok_and(|handle| handle.runtime_flavor() == RuntimeFlavor::MultiThread);
+        if !multi_threaded {
+            return estimate_tokens(text);
+        }
+
+        let tokens = tokio::task::block_in_place(|| {
+            Handle::current().block_on(model.tokenize(Either::Right(text.to_string()), None, false, false, None))
+        });
+        match tokens {
+            Ok(tokens) => tokens.len(),
+            Err(e) => {
+                debug!(error = ?e, "Failed to tokenize, estimating the token count");
+                estimate_tokens(text)
+            }
+        }
+    }
 }
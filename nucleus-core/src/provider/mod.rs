@@ -1,19 +1,27 @@
 //! LLM provider abstraction layer.
 //!
 //! This module defines a common interface for different LLM backends
-//! (Ollama, mistral.rs, etc.) to provide chat completions and embeddings.
+//! (Ollama, mistral.rs, OpenAI-compatible servers, etc.) to provide chat
+//! completions and embeddings.
 
+mod factory;
 pub mod mistralrs;
 pub mod ollama;
+pub mod openai_compat;
+mod think;
 mod types;
 mod utils;
 
 // Re-export common types
 pub use types::{
-    ChatRequest, ChatResponse, EmbedRequest, EmbedResponse, Message, Provider, ProviderError,
-    Result, Tool, ToolCall, ToolCallFunction, ToolFunction,
+    estimate_tokens, ChatRequest, ChatResponse, EmbedRequest, EmbedResponse, Message, Provider,
+    ProviderError, Result, SamplingParams, Tool, ToolCall, ToolCallFunction, ToolFunction, Usage,
 };
 
+pub use factory::ProviderFactory;
+pub(crate) use think::{ThinkDelta, ThinkSplitter};
+
 // Re-export provider implementations
 pub use mistralrs::MistralRsProvider;
 pub use ollama::OllamaProvider;
+pub use openai_compat::OpenAiCompatProvider;
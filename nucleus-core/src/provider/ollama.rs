@@ -4,6 +4,7 @@
 
 use crate::models::EmbeddingModel;
 use super::types::*;
+use super::{ThinkDelta, ThinkSplitter};
 use async_trait::async_trait;
 
 use futures::StreamExt;
@@ -15,7 +16,6 @@ use std::collections::HashMap;
 pub struct OllamaProvider {
     base_url: String,
     http_client: reqwest::Client,
-    config: crate::Config,
 }
 
 impl OllamaProvider {
@@ -24,7 +24,6 @@ impl OllamaProvider {
         Self {
             base_url: config.llm.base_url.clone(),
             http_client: reqwest::Client::new(),
-            config: config.clone(),
         }
     }
 }
@@ -44,29 +43,16 @@ impl Provider for OllamaProvider {
         mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
     ) -> Result<()> {
         let url = format!("{}/api/chat", self.base_url);
+        // Without a token, nothing ever cancels the request
+        let cancel = request.cancel.clone().unwrap_or_default();
         
         // Convert to Ollama-specific request format
         let ollama_request = OllamaChatRequest {
             model: request.model.clone(),
-            messages: request.messages.iter().map(|m| OllamaMessage {
-                role: m.role.clone(),
-                content: m.content.clone(),
-                images: m.images.clone(),
-                tool_calls: m.tool_calls.as_ref().map(|tcs| {
-                    tcs.iter().map(|tc| OllamaToolCall {
-                        function: OllamaToolCallFunction {
-                            name: tc.function.name.clone(),
-                            arguments: tc.function.arguments.clone(),
-                        },
-                    }).collect()
-                }),
-            }).collect(),
-            options: {
-                let mut opts = HashMap::new();
-                opts.insert("temperature".to_string(), serde_json::json!(request.temperature));
-                Some(opts)
-            },
+            messages: request.messages.iter().map(OllamaMessage::from).collect(),
+            options: Some(ollama_options(&request)),
             stream: true,
+            think: request.enable_thinking,
             tools: request.tools.as_ref().map(|tools| {
                 tools.iter().map(|t| OllamaTool {
                     tool_type: t.tool_type.clone(),
@@ -79,11 +65,10 @@ impl Provider for OllamaProvider {
             }),
         };
         
-        let response = self.http_client
-            .post(&url)
-            .json(&ollama_request)
-            .send()
-            .await?;
+        let response = cancel
+            .run_until_cancelled(self.http_client.post(&url).json(&ollama_request).send())
+            .await
+            .ok_or(ProviderError::Cancelled)??;
         
         if !response.status().is_success() {
             let error_text = response.text().await?;
@@ -92,8 +77,15 @@ impl Provider for OllamaProvider {
         
         let mut stream = response.bytes_stream();
         let mut buffer = Vec::new();
+        // Ollama separates reasoning itself only when `think` is set
+        let mut splitter = ThinkSplitter::default();
         
-        while let Some(chunk_result) = stream.next().await {
+        // Dropping the stream closes the connection, which stops generation
+        while let Some(chunk_result) = cancel
+            .run_until_cancelled(stream.next())
+            .await
+            .ok_or(ProviderError::Cancelled)?
+        {
             let chunk = chunk_result?;
             buffer.extend_from_slice(&chunk);
             
@@ -107,25 +99,26 @@ impl Provider for OllamaProvider {
                 let line_str = String::from_utf8_lossy(&line[..line.len()-1]);
                 
                 if let Ok(ollama_response) = serde_json::from_str::<OllamaChatResponse>(&line_str) {
+                    let mut delta = ThinkDelta {
+                        reasoning: ollama_response.message.thinking.clone().unwrap_or_default(),
+                        content: String::new(),
+                    };
+                    delta.append(splitter.push(&ollama_response.message.content));
+                    if ollama_response.done {
+                        delta.append(splitter.finish());
+                    }
+
                     // Convert to common ChatResponse
+                    let mut message = Message::from(&ollama_response.message);
+                    message.content = delta.content.clone();
+                    message.reasoning = (!delta.reasoning.is_empty()).then(|| delta.reasoning.clone());
                     callback(ChatResponse {
                         model: ollama_response.model.clone(),
-                        content: ollama_response.message.content.clone(),
+                        content: delta.content,
+                        reasoning: delta.reasoning,
                         done: ollama_response.done,
-                        message: Message {
-                            role: ollama_response.message.role.clone(),
-                            content: ollama_response.message.content.clone(),
-                            context: None,
-                            images: ollama_response.message.images.clone(),
-                            tool_calls: ollama_response.message.tool_calls.as_ref().map(|tcs| {
-                                tcs.iter().map(|tc| ToolCall {
-                                    function: ToolCallFunction {
-                                        name: tc.function.name.clone(),
-                                        arguments: tc.function.arguments.clone(),
-                                    },
-                                }).collect()
-                            }),
-                        },
+                        message,
+                        usage: ollama_response.usage(),
                     });
                 }
             }
@@ -134,11 +127,11 @@ impl Provider for OllamaProvider {
         Ok(())
     }
     
-    async fn embed(&self, text: &str, _model: &EmbeddingModel) -> Result<Vec<f32>> {
+    async fn embed(&self, text: &str, model: &EmbeddingModel) -> Result<Vec<f32>> {
         let url = format!("{}/api/embed", self.base_url);
         
         let embed_request = EmbedRequest {
-            model: self.config.rag.embedding_model.name.clone(),
+            model: model.name.clone(),
             input: text.to_string(),
         };
         
@@ -160,6 +153,39 @@ impl Provider for OllamaProvider {
             .next()
             .ok_or_else(|| ProviderError::Other("No embeddings returned".to_string()))
     }
+
+    /// Reasoning goes back to Ollama as the messages' `thinking`
+    fn sends_reasoning(&self) -> bool {
+        true
+    }
+}
+
+/// Sampling settings under Ollama's option names.
+fn ollama_options(request: &ChatRequest) -> HashMap<String, serde_json::Value> {
+    let sampling = &request.sampling;
+    let mut opts = HashMap::new();
+    opts.insert("temperature".to_string(), serde_json::json!(request.temperature));
+
+    let optional = [
+        ("top_p", sampling.top_p.map(serde_json::Value::from)),
+        ("top_k", sampling.top_k.map(serde_json::Value::from)),
+        ("min_p", sampling.min_p.map(serde_json::Value::from)),
+        ("repeat_penalty", sampling.repetition_penalty.map(serde_json::Value::from)),
+        ("frequency_penalty", sampling.frequency_penalty.map(serde_json::Value::from)),
+        ("presence_penalty", sampling.presence_penalty.map(serde_json::Value::from)),
+        ("seed", sampling.seed.map(serde_json::Value::from)),
+        ("num_predict", sampling.max_tokens.map(serde_json::Value::from)),
+    ];
+    for (name, value) in optional {
+        if let Some(value) = value {
+            opts.insert(name.to_string(), value);
+        }
+    }
+    if !sampling.stop.is_empty() {
+        opts.insert("stop".to_string(), serde_json::json!(sampling.stop));
+    }
+
+    opts
 }
 
 // Ollama-specific request/response types (internal)
@@ -174,6 +200,10 @@ struct OllamaChatRequest {
     stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<Vec<OllamaTool>>,
+    /// Turns thinking on or off for thinking models, which then report
+    /// their reasoning in `thinking`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    think: Option<bool>,
 }
 
 fn default_stream() -> bool {
@@ -184,10 +214,64 @@ fn default_stream() -> bool {
 struct OllamaMessage {
     role: String,
     content: String,
+    /// Reasoning of an assistant message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    thinking: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     images: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_calls: Option<Vec<OllamaToolCall>>,
+    /// Tool that produced a `tool` message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tool_name: Option<String>,
+    /// Call that a `tool` message answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+}
+
+impl From<&Message> for OllamaMessage {
+    fn from(message: &Message) -> Self {
+        Self {
+            role: message.role.clone(),
+            content: message.content.clone(),
+            thinking: message.reasoning.clone(),
+            images: message.images.clone(),
+            tool_calls: message.tool_calls.as_ref().map(|tcs| {
+                tcs.iter().map(|tc| OllamaToolCall {
+                    id: tc.id.clone(),
+                    function: OllamaToolCallFunction {
+                        name: tc.function.name.clone(),
+                        arguments: tc.function.arguments.clone(),
+                    },
+                }).collect()
+            }),
+            tool_name: message.name.clone(),
+            tool_call_id: message.tool_call_id.clone(),
+        }
+    }
+}
+
+impl From<&OllamaMessage> for Message {
+    fn from(message: &OllamaMessage) -> Self {
+        Self {
+            role: message.role.clone(),
+            content: message.content.clone(),
+            reasoning: message.thinking.clone(),
+            context: None,
+            images: message.images.clone(),
+            tool_calls: message.tool_calls.as_ref().map(|tcs| {
+                tcs.iter().map(|tc| ToolCall {
+                    id: tc.id.clone(),
+                    function: ToolCallFunction {
+                        name: tc.function.name.clone(),
+                        arguments: tc.function.arguments.clone(),
+                    },
+                }).collect()
+            }),
+            tool_call_id: message.tool_call_id.clone(),
+            name: message.tool_name.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -200,6 +284,25 @@ struct OllamaChatResponse {
     done: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     done_reason: Option<String>,
+    /// Tokens in the prompt, sent with the final chunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt_eval_count: Option<usize>,
+    /// Tokens generated, sent with the final chunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    eval_count: Option<usize>,
+}
+
+impl OllamaChatResponse {
+    fn usage(&self) -> Option<Usage> {
+        if self.prompt_eval_count.is_none() && self.eval_count.is_none() {
+            return None;
+        }
+
+        Some(Usage {
+            prompt_tokens: self.prompt_eval_count.unwrap_or_default(),
+            completion_tokens: self.eval_count.unwrap_or_default(),
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -218,6 +321,9 @@ struct OllamaToolFunction {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct OllamaToolCall {
+    /// Only sent by recent Ollama versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
     function: OllamaToolCallFunction,
 }
 
@@ -226,3 +332,161 @@ struct OllamaToolCallFunction {
     name: String,
     arguments: serde_json::Value,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_call_ids_round_trip() {
+        let response: OllamaChatResponse = serde_json::from_str(
+            r#"{"model":"qwen3","message":{"role":"assistant","content":"","tool_calls":[
+                {"id":"call_a","function":{"name":"read_file","arguments":{"path":"a.rs"}}},
+                {"function":{"name":"read_file","arguments":{"path":"b.rs"}}}
+            ]},"done":true}"#,
+        )
+        .unwrap();
+
+        let message = Message::from(&response.message);
+        let tool_calls = message.tool_calls.as_ref().unwrap();
+        assert_eq!(tool_calls[0].id.as_deref(), Some("call_a"));
+        assert_eq!(tool_calls[1].id, None);
+
+        let result = serde_json::to_value(OllamaMessage::from(&Message::tool_result(None, &tool_calls[0], "fn main() {}"))).unwrap();
+        assert_eq!(result["tool_name"], "read_file");
+        assert_eq!(result["tool_call_id"], "call_a");
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_stream() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio_util::sync::CancellationToken;
+
+        // Streams one chunk, then stalls as a slow model would
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 4096];
+            let _ = socket.read(&mut request).await;
+            let chunk = r#"{"model":"qwen3","message":{"role":"assistant","content":"Hel"},"done":false}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nConnection: close\r\n\r\n{}\n",
+                chunk
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+        });
+
+        let mut config = crate::Config::default();
+        config.llm.base_url = format!("http://{}", addr);
+        let provider = OllamaProvider::new(&config);
+
+        let cancel = CancellationToken::new();
+        let request = ChatRequest::new("qwen3", vec![Message::user(None, "Hi")]).with_cancellation(cancel.clone());
+        let mut received = String::new();
+        let result = provider
+            .chat(request, Box::new(|response| {
+                received.push_str(&response.content);
+                cancel.cancel();
+            }))
+            .await;
+
+        assert!(matches!(result, Err(ProviderError::Cancelled)));
+        assert_eq!(received, "Hel");
+    }
+
+    #[test]
+    fn test_sampling_options() {
+        let request = ChatRequest::new("qwen3", vec![]).with_sampling(SamplingParams {
+            top_k: Some(20),
+            repetition_penalty: Some(1.05),
+            max_tokens: Some(256),
+            stop: vec!["</answer>".to_string()],
+            ..SamplingParams::default()
+        });
+        let options = ollama_options(&request);
+
+        assert_eq!(options["top_k"], 20);
+        assert_eq!(options["repeat_penalty"], 1.05);
+        assert_eq!(options["num_predict"], 256);
+        assert_eq!(options["stop"], serde_json::json!(["</answer>"]));
+        assert!(!options.contains_key("seed"));
+    }
+
+    #[tokio::test]
+    async fn test_reasoning_is_separated() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Inline `<think>` content, as sent when `think` isn't set
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 4096];
+            let _ = socket.read(&mut request).await;
+            let chunks = [
+                r#"{"model":"qwen3","message":{"role":"assistant","content":"<think>Greeting"},"done":false}"#,
+                r#"{"model":"qwen3","message":{"role":"assistant","content":"</think>\n\nHello"},"done":false}"#,
+                r#"{"model":"qwen3","message":{"role":"assistant","content":""},"done":true}"#,
+            ];
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nConnection: close\r\n\r\n{}\n",
+                chunks.join("\n")
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let mut config = crate::Config::default();
+        config.llm.base_url = format!("http://{}", addr);
+        let provider = OllamaProvider::new(&config);
+
+        let mut reasoning = String::new();
+        let mut content = String::new();
+        provider
+            .chat(ChatRequest::new("qwen3", vec![Message::user(None, "Hi")]), Box::new(|response| {
+                reasoning.push_str(&response.reasoning);
+                content.push_str(&response.content);
+            }))
+            .await
+            .unwrap();
+
+        assert_eq!(reasoning, "Greeting");
+        assert_eq!(content, "Hello");
+    }
+
+    #[tokio::test]
+    async fn test_embed_uses_requested_model() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Answers with a fixed embedding and reports the request it got
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![0u8; 4096];
+            let read = socket.read(&mut request).await.unwrap();
+            let body = r#"{"model":"mxbai-embed-large","embeddings":[[0.5,0.25]]}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request[..read]).to_string()
+        });
+
+        let mut config = crate::Config::default();
+        config.llm.base_url = format!("http://{}", addr);
+        let provider = OllamaProvider::new(&config);
+
+        let model = EmbeddingModel {
+            name: "mxbai-embed-large".to_string(),
+            ..EmbeddingModel::default()
+        };
+        let embedding = provider.embed("hello", &model).await.unwrap();
+
+        assert_eq!(embedding, vec![0.5, 0.25]);
+        assert!(server.await.unwrap().contains(r#""model":"mxbai-embed-large""#));
+    }
+}
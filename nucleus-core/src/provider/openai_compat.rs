@@ -0,0 +1,564 @@
+//! OpenAI-compatible provider implementation.
+//!
+//! Many local inference servers (llama.cpp's `llama-server`, vLLM, LM Studio,
+//! LocalAI) expose the OpenAI `/v1/chat/completions` and `/v1/embeddings`
+//! endpoints. This provider talks to any of them, with `llm.base_url`
+//! pointing at the API root (usually ending in `/v1`).
+
+use crate::models::EmbeddingModel;
+use super::types::*;
+use super::{ThinkDelta, ThinkSplitter};
+use async_trait::async_trait;
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Texts sent per embeddings request
+const EMBED_BATCH_SIZE: usize = 64;
+
+/// OpenAI-compatible HTTP API provider.
+#[derive(Debug, Clone)]
+pub struct OpenAiCompatProvider {
+    base_url: String,
+    api_key: Option<String>,
+    http_client: reqwest::Client,
+}
+
+impl OpenAiCompatProvider {
+    /// Creates a new provider for the server at `config.llm.base_url`.
+    pub fn new(config: &crate::Config) -> Self {
+        Self {
+            base_url: config.llm.base_url.trim_end_matches('/').to_string(),
+            api_key: config.llm.api_key.clone(),
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        let request = self.http_client.post(format!("{}{}", self.base_url, path));
+        match &self.api_key {
+            Some(api_key) => request.bearer_auth(api_key),
+            None => request,
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for OpenAiCompatProvider {
+    async fn chat<'a>(
+        &'a self,
+        request: ChatRequest,
+        mut callback: Box<dyn FnMut(ChatResponse) + Send + 'a>,
+    ) -> Result<()> {
+        // Without a token, nothing ever cancels the request
+        let cancel = request.cancel.clone().unwrap_or_default();
+
+        let openai_request = OpenAiChatRequest {
+            model: request.model.clone(),
+            messages: request.messages.iter().map(OpenAiMessage::from).collect(),
+            temperature: request.temperature,
+            sampling: request.sampling.clone(),
+            stream: true,
+            stream_options: StreamOptions { include_usage: true },
+            tools: request.tools.clone(),
+            chat_template_kwargs: request
+                .enable_thinking
+                .map(|enable_thinking| ChatTemplateKwargs { enable_thinking }),
+        };
+
+        let response = cancel
+            .run_until_cancelled(self.post("/chat/completions").json(&openai_request).send())
+            .await
+            .ok_or(ProviderError::Cancelled)??;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            return Err(ProviderError::Api(error_text));
+        }
+
+        let mut stream = response.bytes_stream();
+        let mut buffer = Vec::new();
+        let mut content = String::new();
+        let mut reasoning = String::new();
+        // For servers that leave `<think>` blocks in the content
+        let mut splitter = ThinkSplitter::default();
+        let mut tool_calls = ToolCallAssembler::default();
+        let mut usage = None;
+
+        // Server-sent events: `data: {...}` lines, ending with `data: [DONE]`
+        'events: while let Some(chunk_result) = cancel
+            .run_until_cancelled(stream.next())
+            .await
+            .ok_or(ProviderError::Cancelled)?
+        {
+            let chunk = chunk_result?;
+            buffer.extend_from_slice(&chunk);
+
+            while let Some(newline_pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line = buffer.drain(..=newline_pos).collect::<Vec<_>>();
+                let line = String::from_utf8_lossy(&line);
+                let Some(data) = line.trim_end().strip_prefix("data:") else {
+                    continue;
+                };
+
+                let data = data.trim_start();
+                if data == "[DONE]" {
+                    break 'events;
+                }
+
+                let chunk: OpenAiChunk = serde_json::from_str(data)?;
+                if let Some(error) = chunk.error {
+                    return Err(ProviderError::Api(error.to_string()));
+                }
+                if let Some(chunk_usage) = chunk.usage {
+                    usage = Some(Usage {
+                        prompt_tokens: chunk_usage.prompt_tokens,
+                        completion_tokens: chunk_usage.completion_tokens,
+                    });
+                }
+
+                for choice in chunk.choices {
+                    for delta in choice.delta.tool_calls.unwrap_or_default() {
+                        tool_calls.push(delta);
+                    }
+
+                    let mut delta = ThinkDelta {
+                        reasoning: choice.delta.reasoning_content.unwrap_or_default(),
+                        content: String::new(),
+                    };
+                    delta.append(splitter.push(&choice.delta.content.unwrap_or_default()));
+                    if !delta.content.is_empty() || !delta.reasoning.is_empty() {
+                        content.push_str(&delta.content);
+                        reasoning.push_str(&delta.reasoning);
+                        callback(ChatResponse {
+                            model: request.model.clone(),
+                            content: delta.content,
+                            reasoning: delta.reasoning,
+                            done: false,
+                            message: Message::assistant(None, content.clone()),
+                            usage: None,
+                        });
+                    }
+                }
+            }
+        }
+
+        // Send final done=true message. Its content is only text held back as
+        // a possible partial tag; everything else was sent incrementally.
+        let rest = splitter.finish();
+        content.push_str(&rest.content);
+        reasoning.push_str(&rest.reasoning);
+        let mut message = Message::assistant(None, content);
+        message.reasoning = (!reasoning.is_empty()).then_some(reasoning);
+        message.tool_calls = tool_calls.finish();
+        callback(ChatResponse {
+            model: request.model,
+            content: rest.content,
+            reasoning: rest.reasoning,
+            done: true,
+            message,
+            usage,
+        });
+
+        Ok(())
+    }
+
+    async fn embed(&self, text: &str, model: &EmbeddingModel) -> Result<Vec<f32>> {
+        self.embed_batch(&[text], model)
+            .await?
+            .pop()
+            .ok_or_else(|| ProviderError::Other("No embeddings returned".to_string()))
+    }
+
+    async fn embed_batch(&self, texts: &[&str], model: &EmbeddingModel) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+
+        for batch in texts.chunks(EMBED_BATCH_SIZE) {
+            let embed_request = OpenAiEmbedRequest {
+                model: &model.name,
+                input: batch,
+            };
+
+            let response = self.post("/embeddings").json(&embed_request).send().await?;
+            if !response.status().is_success() {
+                let error_text = response.text().await?;
+                return Err(ProviderError::Api(error_text));
+            }
+
+            // Results carry their input's index and aren't guaranteed to be in order
+            let mut data = response.json::<OpenAiEmbedResponse>().await?.data;
+            if data.len() != batch.len() {
+                return Err(ProviderError::Other(format!(
+                    "Expected {} embeddings, got {}",
+                    batch.len(),
+                    data.len()
+                )));
+            }
+            data.sort_by_key(|embedding| embedding.index);
+            embeddings.extend(data.into_iter().map(|embedding| embedding.embedding));
+        }
+
+        Ok(embeddings)
+    }
+}
+
+/// Builds tool calls from streamed deltas.
+///
+/// The first delta of a call carries its id and name; the arguments arrive
+/// as pieces of a JSON string over the following deltas.
+#[derive(Debug, Default)]
+struct ToolCallAssembler {
+    calls: Vec<PartialToolCall>,
+}
+
+#[derive(Debug, Default)]
+struct PartialToolCall {
+    id: Option<String>,
+    name: String,
+    arguments: String,
+}
+
+impl ToolCallAssembler {
+    fn push(&mut self, delta: OpenAiToolCallDelta) {
+        // Servers that omit the index send each call whole
+        let index = delta.index.unwrap_or(self.calls.len());
+        if index >= self.calls.len() {
+            self.calls.resize_with(index + 1, PartialToolCall::default);
+        }
+
+        let call = &mut self.calls[index];
+        if delta.id.is_some() {
+            call.id = delta.id;
+        }
+        if let Some(function) = delta.function {
+            if let Some(name) = function.name {
+                call.name.push_str(&name);
+            }
+            if let Some(arguments) = function.arguments {
+                call.arguments.push_str(&arguments);
+            }
+        }
+    }
+
+    fn finish(self) -> Option<Vec<ToolCall>> {
+        let calls: Vec<ToolCall> = self
+            .calls
+            .into_iter()
+            .filter(|call| !call.name.is_empty())
+            .map(|call| {
+                // Malformed arguments are passed on as a string, so the tool
+                // reports them to the model instead of running with none
+                let arguments = if call.arguments.trim().is_empty() {
+                    Value::Object(Default::default())
+                } else {
+                    serde_json::from_str(&call.arguments).unwrap_or(Value::String(call.arguments))
+                };
+
+                ToolCall {
+                    id: call.id,
+                    function: ToolCallFunction {
+                        name: call.name,
+                        arguments,
+                    },
+                }
+            })
+            .collect();
+
+        (!calls.is_empty()).then_some(calls)
+    }
+}
+
+// OpenAI-specific request/response types (internal)
+
+#[derive(Debug, Clone, Serialize)]
+struct OpenAiChatRequest {
+    model: String,
+    messages: Vec<OpenAiMessage>,
+    temperature: f64,
+    /// Field names match the OpenAI API; `top_k`, `min_p` and
+    /// `repetition_penalty` are extensions most local servers accept
+    #[serde(flatten)]
+    sampling: SamplingParams,
+    stream: bool,
+    stream_options: StreamOptions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<Tool>>,
+    /// Passed to the chat template by llama.cpp and vLLM
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chat_template_kwargs: Option<ChatTemplateKwargs>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ChatTemplateKwargs {
+    /// Read by the templates of thinking models such as Qwen3
+    enable_thinking: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct StreamOptions {
+    /// Ask for a final chunk with token usage
+    include_usage: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct OpenAiMessage {
+    role: String,
+    content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<Vec<OpenAiToolCall>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+}
+
+impl From<&Message> for OpenAiMessage {
+    fn from(message: &Message) -> Self {
+        Self {
+            role: message.role.clone(),
+            content: message.content.clone(),
+            tool_calls: message.tool_calls.as_ref().map(|tcs| {
+                tcs.iter().map(|tc| OpenAiToolCall {
+                    id: tc.id.clone().unwrap_or_default(),
+                    tool_type: "function".to_string(),
+                    function: OpenAiToolCallFunction {
+                        name: tc.function.name.clone(),
+                        // The API expects the arguments as a JSON string
+                        arguments: tc.function.arguments.to_string(),
+                    },
+                }).collect()
+            }),
+            tool_call_id: message.tool_call_id.clone(),
+            name: message.name.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct OpenAiToolCall {
+    id: String,
+    #[serde(rename = "type")]
+    tool_type: String,
+    function: OpenAiToolCallFunction,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct OpenAiToolCallFunction {
+    name: String,
+    arguments: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiChunk {
+    #[serde(default)]
+    choices: Vec<OpenAiChoice>,
+    /// Sent in a final chunk without choices
+    #[serde(default)]
+    usage: Option<OpenAiUsage>,
+    /// Sent instead of choices when generation fails mid-stream
+    #[serde(default)]
+    error: Option<Value>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiChoice {
+    #[serde(default)]
+    delta: OpenAiDelta,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct OpenAiDelta {
+    #[serde(default)]
+    content: Option<String>,
+    /// Reasoning, from servers that separate it; vLLM calls it `reasoning`
+    #[serde(default, alias = "reasoning")]
+    reasoning_content: Option<String>,
+    #[serde(default)]
+    tool_calls: Option<Vec<OpenAiToolCallDelta>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiToolCallDelta {
+    #[serde(default)]
+    index: Option<usize>,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    function: Option<OpenAiFunctionDelta>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiFunctionDelta {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    arguments: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiUsage {
+    prompt_tokens: usize,
+    completion_tokens: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct OpenAiEmbedRequest<'a> {
+    model: &'a str,
+    input: &'a [&'a str],
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiEmbedResponse {
+    data: Vec<OpenAiEmbedding>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::header;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use serde_json::json;
+
+    /// Starts a mock server and returns a provider pointed at it.
+    async fn provider(router: Router) -> OpenAiCompatProvider {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let mut config = crate::Config::default();
+        config.llm.base_url = format!("http://{}/v1/", addr);
+        OpenAiCompatProvider::new(&config)
+    }
+
+    fn sse(events: &[Value]) -> String {
+        let mut body: String = events.iter().map(|event| format!("data: {}\n\n", event)).collect();
+        body.push_str("data: [DONE]\n\n");
+        body
+    }
+
+    #[tokio::test]
+    async fn test_chat_streams_content_and_assembles_tool_calls() {
+        let router = Router::new().route(
+            "/v1/chat/completions",
+            post(|Json(request): Json<Value>| async move {
+                assert_eq!(request["stream"], true);
+                assert_eq!(request["messages"][1]["tool_call_id"], "call_1");
+                assert_eq!(request["messages"][1]["name"], "read_file");
+
+                let body = sse(&[
+                    json!({"choices": [{"delta": {"role": "assistant", "content": "Let me "}}]}),
+                    json!({"choices": [{"delta": {"content": "check."}}]}),
+                    json!({"choices": [{"delta": {"tool_calls": [
+                        {"index": 0, "id": "call_a", "type": "function", "function": {"name": "read_file", "arguments": ""}}
+                    ]}}]}),
+                    json!({"choices": [{"delta": {"tool_calls": [{"index": 0, "function": {"arguments": "{\"path\": "}}]}}]}),
+                    json!({"choices": [{"delta": {"tool_calls": [{"index": 0, "function": {"arguments": "\"a.rs\"}"}}]}}]}),
+                    json!({"choices": [{"delta": {"tool_calls": [
+                        {"index": 1, "id": "call_b", "function": {"name": "list_dir", "arguments": "{}"}}
+                    ]}}]}),
+                    json!({"choices": [], "usage": {"prompt_tokens": 12, "completion_tokens": 7, "total_tokens": 19}}),
+                ]);
+                ([(header::CONTENT_TYPE, "text/event-stream")], body)
+            }),
+        );
+        let provider = provider(router).await;
+
+        let previous_call = ToolCall {
+            id: Some("call_1".to_string()),
+            function: ToolCallFunction {
+                name: "read_file".to_string(),
+                arguments: json!({"path": "b.rs"}),
+            },
+        };
+        let request = ChatRequest::new(
+            "qwen3",
+            vec![
+                Message::user(None, "What is in a.rs?"),
+                Message::tool_result(None, &previous_call, "fn main() {}"),
+            ],
+        );
+
+        let mut responses = Vec::new();
+        provider
+            .chat(request, Box::new(|response| responses.push(response)))
+            .await
+            .unwrap();
+
+        let text: String = responses.iter().map(|response| response.content.as_str()).collect();
+        assert_eq!(text, "Let me check.");
+
+        let last = responses.last().unwrap();
+        assert!(last.done);
+        assert_eq!(last.message.content, "Let me check.");
+        assert_eq!(last.usage, Some(Usage { prompt_tokens: 12, completion_tokens: 7 }));
+
+        let tool_calls = last.message.tool_calls.as_ref().unwrap();
+        assert_eq!(tool_calls.len(), 2);
+        assert_eq!(tool_calls[0].id.as_deref(), Some("call_a"));
+        assert_eq!(tool_calls[0].function.arguments, json!({"path": "a.rs"}));
+        assert_eq!(tool_calls[1].function.name, "list_dir");
+    }
+
+    #[tokio::test]
+    async fn test_reasoning_content_is_separated() {
+        let router = Router::new().route(
+            "/v1/chat/completions",
+            post(|Json(request): Json<Value>| async move {
+                assert_eq!(request["chat_template_kwargs"]["enable_thinking"], true);
+
+                let body = sse(&[
+                    json!({"choices": [{"delta": {"role": "assistant", "reasoning_content": "The user "}}]}),
+                    json!({"choices": [{"delta": {"reasoning_content": "greets me."}}]}),
+                    json!({"choices": [{"delta": {"content": "Hello!"}}]}),
+                ]);
+                ([(header::CONTENT_TYPE, "text/event-stream")], body)
+            }),
+        );
+        let provider = provider(router).await;
+
+        let request = ChatRequest::new("qwen3", vec![Message::user(None, "Hi")]).with_thinking(Some(true));
+        let mut responses = Vec::new();
+        provider
+            .chat(request, Box::new(|response| responses.push(response)))
+            .await
+            .unwrap();
+
+        let reasoning: String = responses.iter().map(|response| response.reasoning.as_str()).collect();
+        assert_eq!(reasoning, "The user greets me.");
+
+        let last = responses.last().unwrap();
+        assert_eq!(last.message.content, "Hello!");
+        assert_eq!(last.message.reasoning.as_deref(), Some("The user greets me."));
+    }
+
+    #[tokio::test]
+    async fn test_embed_batch_orders_by_index() {
+        let router = Router::new().route(
+            "/v1/embeddings",
+            post(|Json(request): Json<Value>| async move {
+                assert_eq!(request["model"], "nomic-embed-text");
+                assert_eq!(request["input"], json!(["first", "second"]));
+                Json(json!({"data": [
+                    {"index": 1, "embedding": [0.0, 1.0]},
+                    {"index": 0, "embedding": [1.0, 0.0]}
+                ]}))
+            }),
+        );
+        let provider = provider(router).await;
+
+        let model = EmbeddingModel {
+            name: "nomic-embed-text".to_string(),
+            ..EmbeddingModel::default()
+        };
+        let embeddings = provider.embed_batch(&["first", "second"], &model).await.unwrap();
+        assert_eq!(embeddings, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+    }
+}
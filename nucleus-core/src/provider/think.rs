@@ -0,0 +1,129 @@
+//! Separating reasoning from answer text.
+//!
+//! Thinking models such as Qwen3 write their reasoning inline, between
+//! `<think>` and `</think>` tags. Providers whose backend doesn't separate it
+//! run the streamed text through a [`ThinkSplitter`], so callers get the
+//! reasoning in [`ChatResponse::reasoning`](super::ChatResponse::reasoning)
+//! and only the answer in `content`.
+
+const THINK_START: &str = "<think>";
+const THINK_END: &str = "</think>";
+
+/// Reasoning and answer text completed by one chunk.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct ThinkDelta {
+    pub(crate) reasoning: String,
+    pub(crate) content: String,
+}
+
+impl ThinkDelta {
+    /// Adds the text of `other` after this delta's.
+    pub(crate) fn append(&mut self, other: ThinkDelta) {
+        self.reasoning.push_str(&other.reasoning);
+        self.content.push_str(&other.content);
+    }
+}
+
+/// Splits streamed text into reasoning and answer text.
+///
+/// Tags may be split across chunks, so a trailing partial tag is held back
+/// until the next chunk shows whether it completes. Whitespace between the
+/// reasoning and the answer is dropped.
+#[derive(Debug, Default)]
+pub(crate) struct ThinkSplitter {
+    in_think: bool,
+    /// A reasoning block just ended, and no answer text followed yet
+    after_think: bool,
+    pending: String,
+}
+
+impl ThinkSplitter {
+    /// Feeds a chunk, returning the reasoning and answer text it completes.
+    pub(crate) fn push(&mut self, chunk: &str) -> ThinkDelta {
+        let mut buffer = std::mem::take(&mut self.pending);
+        buffer.push_str(chunk);
+
+        let mut delta = ThinkDelta::default();
+        loop {
+            let tag = if self.in_think { THINK_END } else { THINK_START };
+            if let Some(pos) = buffer.find(tag) {
+                self.emit(&buffer[..pos], &mut delta);
+                buffer.drain(..pos + tag.len());
+                self.in_think = !self.in_think;
+                self.after_think = !self.in_think;
+                continue;
+            }
+
+            // Tags are ASCII, so a partial tag always starts on a char boundary
+            let partial = (1..tag.len())
+                .rev()
+                .find(|&len| buffer.ends_with(&tag[..len]))
+                .unwrap_or(0);
+            self.pending = buffer.split_off(buffer.len() - partial);
+            self.emit(&buffer, &mut delta);
+            return delta;
+        }
+    }
+
+    /// Flushes text held back as a possible partial tag.
+    pub(crate) fn finish(&mut self) -> ThinkDelta {
+        let pending = std::mem::take(&mut self.pending);
+        let mut delta = ThinkDelta::default();
+        self.emit(&pending, &mut delta);
+        delta
+    }
+
+    fn emit(&mut self, text: &str, delta: &mut ThinkDelta) {
+        if self.in_think {
+            delta.reasoning.push_str(text);
+            return;
+        }
+
+        let text = if self.after_think { text.trim_start() } else { text };
+        if !text.is_empty() {
+            self.after_think = false;
+        }
+        delta.content.push_str(text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(chunks: &[&str]) -> Vec<(String, String)> {
+        let mut splitter = ThinkSplitter::default();
+        let mut deltas: Vec<ThinkDelta> = chunks.iter().map(|chunk| splitter.push(chunk)).collect();
+        deltas.push(splitter.finish());
+
+        deltas
+            .into_iter()
+            .map(|delta| (delta.reasoning, delta.content))
+            .collect()
+    }
+
+    fn pair(reasoning: &str, content: &str) -> (String, String) {
+        (reasoning.to_string(), content.to_string())
+    }
+
+    #[test]
+    fn test_think_split_across_chunks() {
+        assert_eq!(
+            split(&["<thi", "nk>Let me", " check</th", "ink>\n\n", "Answer", " <"]),
+            vec![
+                pair("", ""),
+                pair("Let me", ""),
+                pair(" check", ""),
+                pair("", ""),
+                pair("", "Answer"),
+                pair("", " "),
+                pair("", "<"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_text_without_tags_passes_through() {
+        assert_eq!(split(&["Hello\n", "world"]), vec![pair("", "Hello\n"), pair("", "world"), pair("", "")]);
+    }
+}
@@ -3,6 +3,7 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tokio_util::sync::CancellationToken;
 
 use crate::models::EmbeddingModel;
 
@@ -20,6 +21,12 @@ pub enum ProviderError {
     
     #[error("Provider error: {0}")]
     Other(String),
+
+    #[error("Request cancelled")]
+    Cancelled,
+
+    #[error("Unknown provider: {0}")]
+    UnknownProvider(String),
 }
 
 pub type Result<T> = std::result::Result<T, ProviderError>;
@@ -32,7 +39,9 @@ pub type Result<T> = std::result::Result<T, ProviderError>;
 pub trait Provider: Send + Sync {
     /// Stream a chat completion.
     ///
-    /// The callback is invoked for each chunk of the response.
+    /// The callback is invoked for each chunk of the response. When the
+    /// request's cancellation token fires, the stream is abandoned and
+    /// [`ProviderError::Cancelled`] is returned.
     async fn chat<'a>(
         &'a self,
         request: ChatRequest,
@@ -41,6 +50,22 @@ pub trait Provider: Send + Sync {
     
     /// Generate an embedding vector for the given text.
     async fn embed(&self, text: &str, model: &EmbeddingModel) -> Result<Vec<f32>>;
+
+    /// Count the tokens `text` occupies in the model's context window.
+    ///
+    /// Defaults to [`estimate_tokens`]. Providers with access to the model's
+    /// tokenizer should override this with an exact count.
+    fn count_tokens(&self, text: &str) -> usize {
+        estimate_tokens(text)
+    }
+
+    /// Whether reasoning kept with assistant messages is sent back to the
+    /// model, where it takes space in the context window.
+    ///
+    /// Defaults to `false`: the reasoning is only kept in the history.
+    fn sends_reasoning(&self) -> bool {
+        false
+    }
     
     /// Generate embeddings for multiple texts in batch.
     /// Default implementation calls embed() sequentially.
@@ -53,13 +78,66 @@ pub trait Provider: Send + Sync {
     }
 }
 
+/// Rough token count for `text`, assuming about four characters per token.
+///
+/// Code and non-English text usually need more tokens than this, so budgets
+/// based on it should leave some slack.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Sampling settings beyond temperature.
+///
+/// Unset values use the backend's defaults. Settings a backend doesn't
+/// support are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SamplingParams {
+    /// Sample only from the smallest set of tokens whose probability adds up to this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    /// Sample only from this many of the most likely tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<usize>,
+    /// Drop tokens less likely than this fraction of the most likely token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_p: Option<f64>,
+    /// Penalty for repeating tokens; 1.0 means no penalty. Older configs
+    /// spell it `repitition_penalty`, which is still accepted
+    #[serde(default, alias = "repitition_penalty", skip_serializing_if = "Option::is_none")]
+    pub repetition_penalty: Option<f64>,
+    /// Penalty growing with how often a token already appeared
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f64>,
+    /// Penalty for tokens that already appeared at all
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f64>,
+    /// Seed for reproducible sampling
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Maximum number of tokens to generate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+    /// Generation stops before any of these sequences
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+}
+
 /// Request for chat completion.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatRequest {
     pub model: String,
     pub messages: Vec<Message>,
     pub temperature: f64,
+    #[serde(default)]
+    pub sampling: SamplingParams,
+    /// Whether thinking models reason before answering; `None` leaves it to
+    /// the model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enable_thinking: Option<bool>,
     pub tools: Option<Vec<Tool>>,
+    /// Stops the response when cancelled
+    #[serde(skip)]
+    pub cancel: Option<CancellationToken>,
 }
 
 impl ChatRequest {
@@ -68,7 +146,10 @@ impl ChatRequest {
             model: model.into(),
             messages,
             temperature: 0.7,
+            sampling: SamplingParams::default(),
+            enable_thinking: None,
             tools: None,
+            cancel: None,
         }
     }
     
@@ -76,20 +157,64 @@ impl ChatRequest {
         self.temperature = temperature;
         self
     }
+
+    pub fn with_sampling(mut self, sampling: SamplingParams) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    pub fn with_thinking(mut self, enable_thinking: Option<bool>) -> Self {
+        self.enable_thinking = enable_thinking;
+        self
+    }
     
     pub fn with_tools(mut self, tools: Vec<Tool>) -> Self {
         self.tools = Some(tools);
         self
     }
+
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
 }
 
 /// Response from chat completion (streaming chunk).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatResponse {
     pub model: String,
+    /// Content added by this chunk, without the model's reasoning
     pub content: String,
+    /// Reasoning added by this chunk, for thinking models
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub reasoning: String,
     pub done: bool,
     pub message: Message,
+    /// Token usage, reported by the final chunk when the backend provides it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+}
+
+/// Tokens used by a chat completion.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    /// Tokens in the request, including the conversation history
+    pub prompt_tokens: usize,
+    /// Tokens generated in the response
+    pub completion_tokens: usize,
+}
+
+impl Usage {
+    pub fn total_tokens(&self) -> usize {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+impl std::ops::AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
 }
 
 /// A single message in a chat conversation.
@@ -100,12 +225,24 @@ pub struct Message {
     pub context: Option<String>,
     /// Message input from the user
     pub content: String,
+
+    /// For assistant messages: the model's reasoning before `content`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
     
     #[serde(skip_serializing_if = "Option::is_none")]
     pub images: Option<Vec<String>>,
     
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ToolCall>>,
+
+    /// For tool results: id of the [`ToolCall`] this message answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+
+    /// For tool results: name of the tool that produced this message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
 }
 
 impl Message {
@@ -114,8 +251,11 @@ impl Message {
             role: "system".to_string(),
             context: Some(context).unwrap_or(None),
             content: content.into(),
+            reasoning: None,
             images: None,
             tool_calls: None,
+            tool_call_id: None,
+            name: None,
         }
     }
     
@@ -124,8 +264,11 @@ impl Message {
             role: "user".to_string(),
             context: Some(context).unwrap_or(None),
             content: content.into(),
+            reasoning: None,
             images: None,
             tool_calls: None,
+            tool_call_id: None,
+            name: None,
         }
     }
     
@@ -134,8 +277,11 @@ impl Message {
             role: "assistant".to_string(),
             context: Some(context).unwrap_or(None),
             content: content.into(),
+            reasoning: None,
             images: None,
             tool_calls: None,
+            tool_call_id: None,
+            name: None,
         }
     }
     
@@ -144,8 +290,20 @@ impl Message {
             role: "tool".to_string(),
             context: Some(context).unwrap_or(None),
             content: content.into(),
+            reasoning: None,
             images: None,
             tool_calls: None,
+            tool_call_id: None,
+            name: None,
+        }
+    }
+
+    /// Result of `tool_call`, linked to the call by its id and tool name.
+    pub fn tool_result(context: Option<String>, tool_call: &ToolCall, content: impl Into<String>) -> Self {
+        Self {
+            tool_call_id: tool_call.id.clone(),
+            name: Some(tool_call.function.name.clone()),
+            ..Self::tool(context, content)
         }
     }
 }
@@ -169,6 +327,10 @@ pub struct ToolFunction {
 /// Tool call requested by the LLM.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolCall {
+    /// Identifies the call, so its result can refer to it. Backends that
+    /// don't assign ids leave this empty and the chat loop fills it in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     pub function: ToolCallFunction,
 }
 
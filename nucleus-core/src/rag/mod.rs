@@ -381,13 +381,29 @@ impl RagEngine {
     /// Returns an error if embedding generation fails.
     ///
     pub async fn retrieve_context(&self, query: &str) -> Result<String> {
+        let results = self.retrieve(query).await?;
+        Ok(Self::format_context(&results))
+    }
+
+    /// Searches the knowledge base for the chunks most relevant to `query`.
+    ///
+    /// # Returns
+    ///
+    /// Up to `storage.top_k` results ordered by descending similarity score,
+    /// each carrying its document's `source` metadata. Empty if the knowledge
+    /// base is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if embedding generation or the search fails.
+    pub async fn retrieve(&self, query: &str) -> Result<Vec<SearchResult>> {
         use tracing::{debug, info};
         
         let count = self.store.count().await.unwrap_or(0);
         debug!("Knowledge base count: {}", count);
         if count == 0 {
-            debug!("Knowledge base is empty, returning empty context");
-            return Ok(String::new());
+            debug!("Knowledge base is empty, returning no results");
+            return Ok(Vec::new());
         }
         
         debug!("Generating query embedding for: {}", query);
@@ -400,24 +416,30 @@ impl RagEngine {
             .map_err(|e| RagError::Retrieval(e.to_string()))?;
         
         info!("Found {} results from RAG search", results.len());
+        for (i, result) in results.iter().enumerate() {
+            debug!("Result {}: score={}, source={:?}", 
+                i + 1, 
+                result.score, 
+                result.document.metadata.get("source"));
+        }
         
+        Ok(results)
+    }
+
+    /// Formats search results as context for the LLM prompt.
+    ///
+    /// Produces the format described in [`retrieve_context`](Self::retrieve_context),
+    /// or an empty string if there are no results.
+    pub fn format_context(results: &[SearchResult]) -> String {
         if results.is_empty() {
-            debug!("No results found, returning empty context");
-            return Ok(String::new());
+            return String::new();
         }
         
         let mut context = String::from("\n\nRelevant context from your knowledge base:\n");
-        
         for (i, result) in results.iter().enumerate() {
-            debug!("Result {}: score={}, source={:?}", 
-                i + 1, 
-                result.score, 
-                result.document.metadata.get("source"));
             context.push_str(&format!("\n[{}] {}\n", i + 1, result.document.content));
         }
-        
-        info!("Generated context with {} results", results.len());
-        Ok(context)
+        context
     }
     
     /// Returns the total number of documents (chunks) in the knowledge base.
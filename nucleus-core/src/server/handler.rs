@@ -1,7 +1,9 @@
 use super::types::{Request, RequestType, StreamChunk};
-use crate::{config::Config, provider::Provider, rag};
-use std::{path::Path, sync::Arc};
+use crate::{chat::build_system_prompt, config::Config, provider::{Provider, ProviderError}, rag};
+use nucleus_plugin::{Permission, PluginRegistry};
+use std::{path::{Path, PathBuf}, sync::Arc};
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
 pub type ChunkSender = mpsc::UnboundedSender<StreamChunk>;
 
@@ -13,8 +15,13 @@ pub struct RequestHandler {
 }
 
 impl RequestHandler {
-    pub async fn new(config: Config, provider: Arc<dyn Provider>) -> Result<Self, rag::RagError> {
-        let rag_manager = rag::RagEngine::new(&config, provider.clone()).await?;
+    /// Creates a handler that chats with `provider` and indexes with `embedder`.
+    pub async fn new(
+        config: Config,
+        provider: Arc<dyn Provider>,
+        embedder: Arc<dyn Provider>,
+    ) -> Result<Self, rag::RagError> {
+        let rag_manager = rag::RagEngine::new(&config, embedder).await?;
         
         Ok(Self {
             config,
@@ -24,31 +31,43 @@ impl RequestHandler {
     }
     
     /// Routes request to appropriate handler based on type.
-    pub async fn handle(&self, request: Request, sender: ChunkSender) {
+    ///
+    /// Chat and edit requests stop early when `cancel` is cancelled.
+    pub async fn handle(&self, request: Request, sender: ChunkSender, cancel: CancellationToken) {
         match request.request_type {
             RequestType::Chat | RequestType::Edit => {
-                self.handle_chat(request, sender).await
+                self.handle_chat(request, sender, cancel).await
             }
             RequestType::Add => self.handle_add(request, sender).await,
             RequestType::Index => self.handle_index(request, sender).await,
             RequestType::Stats => self.handle_stats(sender).await,
+            RequestType::Cancel => {
+                let _ = sender.send(StreamChunk::error("No request in progress to cancel"));
+            }
         }
     }
     
-    async fn handle_chat(&self, request: Request, sender: ChunkSender) {
+    async fn handle_chat(&self, request: Request, sender: ChunkSender, cancel: CancellationToken) {
         use crate::provider::ChatRequest;
         
         let messages = self.build_messages(request);
         
         let chat_request = ChatRequest::new(&self.config.llm.model, messages)
-            .with_temperature(self.config.llm.temperature);
+            .with_temperature(self.config.llm.temperature)
+            .with_sampling(self.config.llm.sampling.clone())
+            .with_thinking(self.config.llm.enable_thinking)
+            .with_cancellation(cancel);
         
         let mut full_response = String::new();
+        let stream_reasoning = self.config.llm.reasoning.stream;
         
         let result = self.provider.chat(chat_request, Box::new(|response| {
-            if !response.message.content.is_empty() {
-                full_response.push_str(&response.message.content);
-                let _ = sender.send(StreamChunk::chunk(&response.message.content));
+            if stream_reasoning && !response.reasoning.is_empty() {
+                let _ = sender.send(StreamChunk::reasoning(&response.reasoning));
+            }
+            if !response.content.is_empty() {
+                full_response.push_str(&response.content);
+                let _ = sender.send(StreamChunk::chunk(&response.content));
             }
         })).await;
         
@@ -56,6 +75,9 @@ impl RequestHandler {
             Ok(_) => {
                 let _ = sender.send(StreamChunk::done(&full_response));
             }
+            Err(ProviderError::Cancelled) => {
+                let _ = sender.send(StreamChunk::cancelled(&full_response));
+            }
             Err(e) => {
                 let _ = sender.send(StreamChunk::error(e.to_string()));
             }
@@ -100,7 +122,16 @@ impl RequestHandler {
     fn build_messages(&self, request: Request) -> Vec<crate::provider::Message> {
         use crate::provider::Message;
         
-        let mut messages = vec![Message::system(None, &self.config.system_prompt)];
+        // Template variables refer to the client's directory when it sends one.
+        // The server doesn't run tools, so none are listed.
+        let cwd = match &request.pwd {
+            Some(pwd) => PathBuf::from(pwd),
+            None => std::env::current_dir().unwrap_or_default(),
+        };
+        let registry = PluginRegistry::new(Permission::NONE);
+        let system_prompt = build_system_prompt(&self.config.system_prompt, &registry, &cwd);
+
+        let mut messages = vec![Message::system(None, system_prompt)];
         
         if let Some(history) = request.history {
             for msg in history {
@@ -108,8 +139,11 @@ impl RequestHandler {
                     role: "user".to_string(),
                     context: None,
                     content: msg.content.clone(),
+                    reasoning: None,
                     images: None,
                     tool_calls: None,
+                    tool_call_id: None,
+                    name: None,
                 });
             }
         }
@@ -13,10 +13,13 @@ mod types;
 #[allow(unused)]
 pub use types::{ChunkType, Message, Request, RequestType, StreamChunk};
 
-use crate::{config::Config, detection, provider::{OllamaProvider, Provider}};
+use crate::{config::{Config, ProviderKind}, detection, provider::ProviderFactory};
+use nucleus_plugin::{Permission, PluginRegistry};
 use std::sync::Arc;
+use tokio::io::BufReader;
 use tokio::signal;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
 #[cfg(unix)]
 const SOCKET_PATH: &str = "/tmp/llm-workspace.sock";
@@ -31,16 +34,37 @@ pub struct Server {
 }
 
 impl Server {
-    /// Creates a new server instance.
+    /// Creates a new server instance using the provider selected by `llm.provider`,
+    /// or Ollama if it isn't set.
     /// 
-    /// This will check if Ollama is installed and running.
+    /// With the Ollama provider, this will check if Ollama is installed and running.
     /// If not, helpful installation/startup instructions will be printed.
     /// Connects to Qdrant for persistent vector storage.
     pub async fn new(config: Config) -> Result<Self, Box<dyn std::error::Error>> {
-        detection::detect_ollama()?;
+        Self::with_provider_factory(config, ProviderFactory::new()).await
+    }
+    
+    /// Creates a new server instance, creating its provider with `factory`.
+    ///
+    /// Needed to serve a custom provider; see [`ProviderFactory`].
+    pub async fn with_provider_factory(
+        mut config: Config,
+        factory: ProviderFactory,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        // The server has always run on Ollama, so configs written for it don't name a provider
+        let provider = config.llm.provider.get_or_insert(ProviderKind::Ollama);
+        if *provider == ProviderKind::Ollama {
+            detection::detect_ollama()?;
+        }
         
-        let provider: Arc<dyn Provider> = Arc::new(OllamaProvider::new(&config));
-        let handler = Arc::new(handler::RequestHandler::new(config, provider).await?);
+        // The server doesn't run tools
+        let registry = Arc::new(PluginRegistry::new(Permission::NONE));
+        let provider = factory.create(&config, Arc::clone(&registry)).await?;
+        let embedder = factory
+            .create_embedding(&config, registry)
+            .await?
+            .unwrap_or_else(|| provider.clone());
+        let handler = Arc::new(handler::RequestHandler::new(config, provider, embedder).await?);
         let transport = transport::IpcTransport::new(SOCKET_PATH);
         
         Ok(Self { handler, transport })
@@ -78,23 +102,53 @@ impl Server {
 }
 
 /// Handles a single client connection.
+///
+/// While a request runs, the client can send a `cancel` request on the same
+/// connection to stop it. Disconnecting stops it as well.
 async fn handle_connection(
-    mut stream: transport::IpcStream,
+    stream: transport::IpcStream,
     handler: Arc<handler::RequestHandler>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let request = transport::read_request(&mut stream).await?;
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    let Some(request) = transport::read_request(&mut reader).await? else {
+        return Ok(());
+    };
     
+    let cancel = CancellationToken::new();
     let (sender, receiver) = mpsc::unbounded_channel();
     
-    let handle_task = tokio::spawn(async move {
-        handler.handle(request, sender).await;
+    let watch_task = tokio::spawn({
+        let cancel = cancel.clone();
+        async move {
+            while let Ok(Some(request)) = transport::read_request(&mut reader).await {
+                if request.request_type == RequestType::Cancel {
+                    cancel.cancel();
+                    break;
+                }
+            }
+        }
+    });
+    
+    let handle_task = tokio::spawn({
+        let cancel = cancel.clone();
+        async move {
+            handler.handle(request, sender, cancel).await;
+        }
     });
     
     let write_task = tokio::spawn(async move {
-        transport::write_chunks(&mut stream, receiver).await
+        let result = transport::write_chunks(&mut writer, receiver).await;
+        if result.is_err() {
+            // The client is gone, so there is no one to answer
+            cancel.cancel();
+        }
+        result
     });
     
-    let _ = tokio::try_join!(handle_task, write_task)?;
+    let result = tokio::try_join!(handle_task, write_task);
+    watch_task.abort();
+    let _ = result?;
     
     Ok(())
 }
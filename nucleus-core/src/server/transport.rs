@@ -1,6 +1,6 @@
 use super::types::{Request, StreamChunk};
 use thiserror::Error;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::sync::mpsc;
 
 #[cfg(unix)]
@@ -105,35 +105,31 @@ impl IpcTransport {
     }
 }
 
-/// Reads a request from the stream.
-#[cfg(unix)]
-pub async fn read_request(stream: &mut IpcStream) -> Result<Request> {
-    let (reader, _) = stream.split();
-    let mut reader = BufReader::new(reader);
+/// Reads the next request from the stream.
+///
+/// Returns `None` once the client has stopped sending.
+pub async fn read_request<R>(reader: &mut R) -> Result<Option<Request>>
+where
+    R: AsyncBufRead + Unpin,
+{
     let mut line = String::new();
     
-    reader.read_line(&mut line).await?;
-    let request = serde_json::from_str(&line)?;
-    
-    Ok(request)
-}
-
-#[cfg(windows)]
-pub async fn read_request(stream: &mut IpcStream) -> Result<Request> {
-    let mut reader = BufReader::new(stream);
-    let mut line = String::new();
-    
-    reader.read_line(&mut line).await?;
+    if reader.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
     let request = serde_json::from_str(&line)?;
     
-    Ok(request)
+    Ok(Some(request))
 }
 
 /// Writes stream chunks to the client.
-pub async fn write_chunks(
-    stream: &mut IpcStream,
+pub async fn write_chunks<W>(
+    stream: &mut W,
     mut receiver: mpsc::UnboundedReceiver<StreamChunk>,
-) -> Result<()> {
+) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
     while let Some(chunk) = receiver.recv().await {
         let json = serde_json::to_string(&chunk)?;
         stream.write_all(json.as_bytes()).await?;
@@ -14,6 +14,8 @@ pub enum RequestType {
     Index,
     /// Get knowledge base statistics
     Stats,
+    /// Cancel the request in progress on the same connection
+    Cancel,
 }
 
 /// Type of streaming response chunk.
@@ -22,10 +24,15 @@ pub enum RequestType {
 pub enum ChunkType {
     /// Partial response content (multiple chunks per request)
     Chunk,
+    /// Partial reasoning of a thinking model, sent before the response
+    /// content if `llm.reasoning.stream` is set
+    Reasoning,
     /// Final response with complete content
     Done,
     /// An error occurred
     Error,
+    /// The request was cancelled; content generated until then
+    Cancelled,
 }
 
 /// A message in conversation history.
@@ -59,7 +66,7 @@ pub struct Request {
     /// For chat/edit: the user's message
     /// For add: the text to add to knowledge base
     /// For index: the directory path to index
-    /// For stats: ignored
+    /// For stats and cancel: ignored
     pub content: String,
 
     /// Optional working directory context.
@@ -87,8 +94,10 @@ pub struct StreamChunk {
     /// The content of this chunk.
     ///
     /// For "chunk" type: partial response text
+    /// For "reasoning" type: partial reasoning text
     /// For "done" type: complete response text
     /// For "error" type: empty (error details in `error` field)
+    /// For "cancelled" type: response text generated before cancellation
     pub content: String,
 
     /// Error message if chunk_type is "error".
@@ -105,6 +114,14 @@ impl StreamChunk {
         }
     }
 
+    pub fn reasoning(content: impl Into<String>) -> Self {
+        Self {
+            chunk_type: ChunkType::Reasoning,
+            content: content.into(),
+            error: None,
+        }
+    }
+
     pub fn done(content: impl Into<String>) -> Self {
         Self {
             chunk_type: ChunkType::Done,
@@ -113,6 +130,14 @@ impl StreamChunk {
         }
     }
 
+    pub fn cancelled(content: impl Into<String>) -> Self {
+        Self {
+            chunk_type: ChunkType::Cancelled,
+            content: content.into(),
+            error: None,
+        }
+    }
+
     pub fn error(error: impl Into<String>) -> Self {
         Self {
             chunk_type: ChunkType::Error,
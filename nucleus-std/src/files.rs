@@ -76,8 +76,8 @@ impl Plugin for ReadFilePlugin {
             .await
             .map_err(|e| PluginError::ExecutionFailed(format!("Failed to read file: {}", e)))?;
         
-        // Log the operation
-        println!("Read file: {}", path.display());
+        // Log the operation (to stderr, stdout may carry a protocol such as MCP stdio)
+        eprintln!("Read file: {}", path.display());
         
         Ok(PluginOutput::new(content))
     }
@@ -124,7 +124,7 @@ impl Plugin for WriteFilePlugin {
             .await
             .map_err(|e| PluginError::ExecutionFailed(format!("Failed to write file: {}", e)))?;
         
-        println!("Wrote file: {}", path.display());
+        eprintln!("Wrote file: {}", path.display());
         
         Ok(PluginOutput::new(format!("Successfully wrote {} bytes to {}", params.content.len(), path.display())))
     }